@@ -1,6 +1,9 @@
 use find_folder::Search;
 use find_folder::Error;
 use std::path::PathBuf;
+use mapping::Tile;
+use sprites;
+use sprites::Sprites;
 
 pub fn find_assets_folder() -> PathBuf {
    let p = Search::KidsThenParents(3, 3).for_folder("assets");
@@ -12,4 +15,34 @@ pub fn find_assets_folder() -> PathBuf {
 
 pub fn find_asset(filename: &str) -> PathBuf {
    find_assets_folder().join(filename)
-}
\ No newline at end of file
+}
+
+pub fn texture_files() -> Vec<(Tile, &'static str)> {
+   vec![
+      (Tile::Empty, "empty.png"),
+      (Tile::Player, "man.png"),
+      (Tile::Mud, "mud.png"),
+      (Tile::Wall, "wall.png"),
+      (Tile::Boulder, "boulder.png"),
+      (Tile::Diamond, "diamond.png"),
+      (Tile::Monster, "butterfly.png"),
+      (Tile::Exit, "exit.png")]
+}
+
+pub fn default_sprites() -> Sprites {
+   Sprites::load(find_asset(sprites::MANIFEST)).unwrap_or_else(|err| {
+      println!("Could not load sprites: {}", err);
+      Sprites::default()
+   })
+}
+
+pub fn texture_paths(overrides: &[(Tile, PathBuf)]) -> Vec<(Tile, PathBuf)> {
+   texture_files().into_iter()
+      .map(|(t, f)| {
+         match overrides.iter().find(|&&(o, _)| o == t) {
+            Some(&(_, ref path)) => (t, path.clone()),
+            None => (t, find_asset(f))
+         }
+      })
+      .collect()
+}
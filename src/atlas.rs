@@ -0,0 +1,81 @@
+use mapping::Tile;
+use render::RgbaImage;
+use sprites::Animation;
+use std::cmp::max;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+
+pub const TILE_KINDS: usize = 8;
+pub const MIN_WIDTH: usize = 256;
+pub const PADDING: usize = 1;
+
+pub struct Atlas {
+   pub image: RgbaImage,
+   pub tiles: [[f64; 4]; TILE_KINDS],
+   pub sheets: HashMap<PathBuf, [f64; 4]>,
+   pub tile_size: (usize, usize)
+}
+
+enum Entry {
+   Tile(Tile),
+   Sheet(PathBuf)
+}
+
+impl Atlas {
+   pub fn build(textures: &[(Tile, PathBuf)], sheets: &[PathBuf]) -> io::Result<Atlas> {
+      let mut entries = Vec::new();
+      for &(t, ref path) in textures {
+         entries.push((Entry::Tile(t), RgbaImage::load_png(path)?));
+      }
+      for path in sheets {
+         entries.push((Entry::Sheet(path.clone()), RgbaImage::load_png(path)?));
+      }
+      let tile_size = entries.iter()
+         .filter(|e| if let Entry::Tile(_) = e.0 { true } else { false })
+         .fold((1, 1), |size, e| (max(size.0, e.1.width), max(size.1, e.1.height)));
+      entries.sort_by(|a, b| b.1.height.cmp(&a.1.height));
+      let width = entries.iter().map(|e| e.1.width + PADDING).fold(MIN_WIDTH, max);
+      let mut placed = Vec::new();
+      let (mut x, mut y, mut shelf) = (0, 0, 0);
+      for (entry, image) in entries {
+         if x + image.width > width {
+            x = 0;
+            y += shelf + PADDING;
+            shelf = 0;
+         }
+         let (w, h) = (image.width, image.height);
+         placed.push((entry, image, x, y));
+         x += w + PADDING;
+         shelf = max(shelf, h);
+      }
+      let height = y + shelf;
+      let mut atlas = Atlas {
+         image: RgbaImage::new(width, max(height, 1)),
+         tiles: [[0.0; 4]; TILE_KINDS],
+         sheets: HashMap::new(),
+         tile_size
+      };
+      for (entry, image, x, y) in placed {
+         atlas.image.blit(&image, x as i64, y as i64);
+         let rect = [x as f64, (atlas.image.height - y - image.height) as f64, image.width as f64, image.height as f64];
+         match entry {
+            Entry::Tile(t) => atlas.tiles[t as usize] = rect,
+            Entry::Sheet(path) => {
+               atlas.sheets.insert(path, rect);
+            }
+         }
+      }
+      Ok(atlas)
+   }
+
+   pub fn tile_rect(&self, tile: Tile) -> [f64; 4] {
+      self.tiles[tile as usize]
+   }
+
+   pub fn frame_rect(&self, animation: &Animation, frame: usize) -> Option<[f64; 4]> {
+      let sheet = self.sheets.get(&animation.sheet)?;
+      let rect = animation.frame_rect((sheet[2] as u32, sheet[3] as u32), frame);
+      Some([sheet[0] + rect[0], sheet[1] + rect[1], rect[2], rect[3]])
+   }
+}
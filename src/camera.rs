@@ -0,0 +1,105 @@
+use mapping::*;
+use pos::*;
+use rand;
+use rand::Rng;
+
+pub const EASING: f64 = 6.0;
+pub const SHAKE_DECAY: f64 = 12.0;
+pub const IMPACT_SHAKE: f64 = 3.0;
+pub const MAX_SHAKE: f64 = 8.0;
+
+pub struct Camera {
+   pub center: (f64, f64),
+   pub dead_zone: (f64, f64),
+   pub easing: f64,
+   pub shake_enabled: bool,
+   shake: f64
+}
+
+impl Camera {
+   pub fn new(dead_zone: (f64, f64), shake_enabled: bool) -> Camera {
+      Camera { center: (0.0, 0.0), dead_zone, easing: EASING, shake_enabled, shake: 0.0 }
+   }
+
+   pub fn jump(&mut self, focus: (f64, f64), map_size: (f64, f64), view_size: (f64, f64), wrap: bool) {
+      self.center = focus;
+      self.shake = 0.0;
+      self.clamp(map_size, view_size, wrap);
+   }
+
+   pub fn update(&mut self, focus: (f64, f64), map_size: (f64, f64), view_size: (f64, f64), wrap: bool, dt: f64) {
+      let focus =
+         if wrap {
+            (nearest_wrapped(focus.0, self.center.0, map_size.0), nearest_wrapped(focus.1, self.center.1, map_size.1))
+         } else {
+            focus
+         };
+      let goal = (
+         follow_axis(self.center.0, focus.0, self.dead_zone.0 / 2.0),
+         follow_axis(self.center.1, focus.1, self.dead_zone.1 / 2.0));
+      let t = 1.0 - (-self.easing * dt).exp();
+      self.center.0 += (goal.0 - self.center.0) * t;
+      self.center.1 += (goal.1 - self.center.1) * t;
+      self.clamp(map_size, view_size, wrap);
+      self.shake = (self.shake - SHAKE_DECAY * dt).max(0.0);
+   }
+
+   pub fn shake(&mut self, strength: f64) {
+      if self.shake_enabled {
+         self.shake = (self.shake + strength).min(MAX_SHAKE);
+      }
+   }
+
+   pub fn translation(&self, view_size: (f64, f64)) -> (f64, f64) {
+      let (mut x, mut y) = (view_size.0 / 2.0 - self.center.0, view_size.1 / 2.0 - self.center.1);
+      if self.shake > 0.0 {
+         let mut rng = rand::thread_rng();
+         x += rng.gen_range(-self.shake, self.shake);
+         y += rng.gen_range(-self.shake, self.shake);
+      }
+      (x.round(), y.round())
+   }
+
+   pub fn view(&self, view_size: (f64, f64)) -> [f64; 4] {
+      [self.center.0 - view_size.0 / 2.0, self.center.1 - view_size.1 / 2.0, view_size.0, view_size.1]
+   }
+
+   fn clamp(&mut self, map_size: (f64, f64), view_size: (f64, f64), wrap: bool) {
+      if wrap {
+         self.center = (self.center.0.rem_euclid(map_size.0), self.center.1.rem_euclid(map_size.1));
+      } else {
+         self.center = (clamp_axis(self.center.0, map_size.0, view_size.0), clamp_axis(self.center.1, map_size.1, view_size.1));
+      }
+   }
+}
+
+pub fn boulder_impacts(m: &LevelMap) -> usize {
+   m.iter_points()
+      .filter(|&p| {
+         let c = m[p];
+         c.tile == Tile::Boulder && c.cool_down == 0 && c.moved_from == Some(m.to(p, Direction::Up))
+      })
+      .count()
+}
+
+fn follow_axis(center: f64, focus: f64, half_zone: f64) -> f64 {
+   if focus < center - half_zone {
+      focus + half_zone
+   } else if focus > center + half_zone {
+      focus - half_zone
+   } else {
+      center
+   }
+}
+
+fn clamp_axis(center: f64, map_len: f64, view_len: f64) -> f64 {
+   if map_len <= view_len {
+      map_len / 2.0
+   } else {
+      center.max(view_len / 2.0).min(map_len - view_len / 2.0)
+   }
+}
+
+fn nearest_wrapped(focus: f64, center: f64, map_len: f64) -> f64 {
+   focus - ((focus - center) / map_len).round() * map_len
+}
@@ -0,0 +1,204 @@
+use mapgen::DIFFICULTIES;
+use std::str::FromStr;
+
+pub const MAX_ZOOM: u32 = 8;
+
+pub struct Options {
+   pub level: usize,
+   pub level_file: Option<String>,
+   pub pack: Option<String>,
+   pub seed: Option<usize>,
+   pub lives: i64,
+   pub difficulty: usize,
+   pub window_size: (u32, u32),
+   pub fullscreen: bool,
+   pub dead_zone: (f64, f64),
+   pub zoom: u32,
+   pub shake: bool,
+   pub tick_rate: u64,
+   pub record: Option<String>,
+   pub play: Option<String>,
+   pub headless: bool,
+   pub terminal: bool,
+   pub screenshot: Option<String>,
+   pub gif: Option<String>,
+   pub gif_scale: usize,
+   pub gif_frame_skip: usize,
+   pub controls: String,
+   pub edit: Option<String>,
+   pub benchmark: Option<(u32, u32)>,
+   pub help: bool
+}
+
+impl Default for Options {
+   fn default() -> Self {
+      Options {
+         level: 0,
+         level_file: None,
+         pack: None,
+         seed: None,
+         lives: 3,
+         difficulty: 0,
+         window_size: (1280, 720),
+         fullscreen: false,
+         dead_zone: (160.0, 96.0),
+         zoom: 1,
+         shake: true,
+         tick_rate: 120,
+         record: None,
+         play: None,
+         headless: false,
+         terminal: false,
+         screenshot: None,
+         gif: None,
+         gif_scale: 1,
+         gif_frame_skip: 6,
+         controls: "controls.cfg".to_string(),
+         edit: None,
+         benchmark: None,
+         help: false
+      }
+   }
+}
+
+impl Options {
+   pub fn parse(args: &[String]) -> Result<Options, String> {
+      let mut options = Options::default();
+      let mut i = 0;
+      while i < args.len() {
+         let arg = args[i].as_str();
+         {
+            let mut value = || {
+               i += 1;
+               args.get(i).cloned().ok_or(format!("Missing value for {}.", arg))
+            };
+            match arg {
+               "-h" | "--help" => options.help = true,
+               "--level" => {
+                  let level: usize = parse_value(arg, &value()?)?;
+                  if level == 0 {
+                     return Err("Levels are numbered from 1.".to_string());
+                  }
+                  options.level = level - 1;
+               }
+               "--level-file" => options.level_file = Some(value()?),
+               "--pack" => options.pack = Some(value()?),
+               "--seed" => options.seed = Some(parse_value(arg, &value()?)?),
+               "--lives" => {
+                  options.lives = parse_value(arg, &value()?)?;
+                  if options.lives < 1 {
+                     return Err("Lives must be at least 1.".to_string());
+                  }
+               }
+               "--difficulty" => {
+                  let difficulty: usize = parse_value(arg, &value()?)?;
+                  if difficulty == 0 || difficulty > DIFFICULTIES {
+                     return Err(format!("Difficulty must be between 1 and {}.", DIFFICULTIES));
+                  }
+                  options.difficulty = difficulty - 1;
+               }
+               "--window-size" => options.window_size = parse_size(arg, &value()?)?,
+               "--fullscreen" => options.fullscreen = true,
+               "--dead-zone" => {
+                  let (w, h) = parse_size(arg, &value()?)?;
+                  options.dead_zone = (w as f64, h as f64);
+               }
+               "--no-shake" => options.shake = false,
+               "--zoom" => {
+                  options.zoom = parse_value(arg, &value()?)?;
+                  if options.zoom == 0 || options.zoom > MAX_ZOOM {
+                     return Err(format!("Zoom must be between 1 and {}.", MAX_ZOOM));
+                  }
+               }
+               "--tick-rate" => {
+                  options.tick_rate = parse_value(arg, &value()?)?;
+                  if options.tick_rate == 0 || options.tick_rate > 1000 {
+                     return Err("Tick rate must be between 1 and 1000.".to_string());
+                  }
+               }
+               "--record" => options.record = Some(value()?),
+               "--play" => options.play = Some(value()?),
+               "--headless" => options.headless = true,
+               "--terminal" => options.terminal = true,
+               "--screenshot" => options.screenshot = Some(value()?),
+               "--gif" => options.gif = Some(value()?),
+               "--gif-scale" => options.gif_scale = parse_value(arg, &value()?)?,
+               "--gif-frame-skip" => options.gif_frame_skip = parse_value(arg, &value()?)?,
+               "--controls" => options.controls = value()?,
+               "--edit" => options.edit = Some(value()?),
+               "--benchmark" => {
+                  let (w, h) = parse_size(arg, &value()?)?;
+                  if w < 5 || h < 5 {
+                     return Err("Benchmark caves must be at least 5x5.".to_string());
+                  }
+                  options.benchmark = Some((w, h));
+               }
+               _ => return Err(format!("Unknown option {}.", arg))
+            }
+         }
+         i += 1;
+      }
+      if options.level_file.is_some() && options.pack.is_some() {
+         return Err("Use either --level-file or --pack, not both.".to_string());
+      }
+      if options.headless && options.play.is_none() {
+         return Err("--headless needs a replay to --play.".to_string());
+      }
+      if options.gif.is_some() && options.play.is_none() {
+         return Err("--gif needs a replay to --play.".to_string());
+      }
+      if options.record.is_some() && options.play.is_some() {
+         return Err("Use either --record or --play, not both.".to_string());
+      }
+      if options.edit.is_some() && (options.play.is_some() || options.terminal || options.screenshot.is_some()) {
+         return Err("--edit cannot be combined with --play, --terminal or --screenshot.".to_string());
+      }
+      Ok(options)
+   }
+}
+
+fn parse_value<T: FromStr>(option: &str, value: &str) -> Result<T, String> {
+   value.parse().map_err(|_| format!("Value for {} not recognised. {}", option, value))
+}
+
+fn parse_size(option: &str, value: &str) -> Result<(u32, u32), String> {
+   let parts: Vec<&str> = value.split('x').collect();
+   if parts.len() == 2 {
+      if let (Ok(w), Ok(h)) = (parts[0].parse(), parts[1].parse()) {
+         if w > 0 && h > 0 {
+            return Ok((w, h));
+         }
+      }
+   }
+   Err(format!("Value for {} not recognised. {}", option, value))
+}
+
+pub fn usage() -> &'static str {
+"Usage: boulderdash [options]
+
+Options:
+   --level N              start on level N (default 1)
+   --level-file PATH      play a single level from a text file
+   --pack PATH            play a level pack directory or .tar archive
+   --seed N               seed the game's random number generator
+   --lives N              number of lives (default 3)
+   --difficulty N         difficulty from 1 to 5 (default 1)
+   --window-size WxH      window size (default 1280x720)
+   --fullscreen           start in fullscreen
+   --dead-zone WxH        camera dead zone in pixels (default 160x96)
+   --no-shake             turn off screen shake
+   --zoom N               integer tile scale from 1 to 8 (default 1, F10/F11 in game)
+   --tick-rate N          game updates per second (default 120)
+   --record PATH          record input to a replay file
+   --play PATH            play back a replay file
+   --headless             run the --play replay without a window and print the final state
+   --terminal             play in the terminal
+   --screenshot PATH      save a PNG of the starting level and exit
+   --gif PATH             save the --play replay's first level as an animated GIF and exit
+   --gif-scale N          GIF pixel scale (default 1)
+   --gif-frame-skip N     game updates per GIF frame (default 6)
+   --controls PATH        key bindings file (default controls.cfg)
+   --edit PATH            open the level editor on a level file, created if missing
+   --benchmark WxH        time map rendering on a generated cave of the given size
+   -h, --help             show this message"
+}
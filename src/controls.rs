@@ -0,0 +1,268 @@
+use piston_window::Key;
+use pos::Direction;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+   Move(Direction),
+   Snap,
+   Pause,
+   RestartLevel,
+   Suicide,
+   Minimap
+}
+
+pub const ACTIONS: [Action; 9] = [
+   Action::Move(Direction::Up),
+   Action::Move(Direction::Down),
+   Action::Move(Direction::Left),
+   Action::Move(Direction::Right),
+   Action::Snap,
+   Action::Pause,
+   Action::RestartLevel,
+   Action::Suicide,
+   Action::Minimap];
+
+pub const MENU_KEY: Key = Key::F1;
+pub const CONFIRM_KEY: Key = Key::Return;
+pub const BACK_KEY: Key = Key::Backspace;
+pub const QUICK_SAVE_KEY: Key = Key::F8;
+pub const QUICK_LOAD_KEY: Key = Key::F9;
+pub const ZOOM_OUT_KEY: Key = Key::F10;
+pub const ZOOM_IN_KEY: Key = Key::F11;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Control {
+   Key(Key),
+   Button(u8)
+}
+
+const BINDABLE_KEYS: [Key; 60] = [
+   Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J,
+   Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T,
+   Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+   Key::D0, Key::D1, Key::D2, Key::D3, Key::D4, Key::D5, Key::D6, Key::D7, Key::D8, Key::D9,
+   Key::Up, Key::Down, Key::Left, Key::Right,
+   Key::Space, Key::Tab, Key::Backspace, Key::Delete, Key::Insert, Key::Home, Key::End,
+   Key::PageUp, Key::PageDown,
+   Key::LShift, Key::RShift, Key::LCtrl, Key::RCtrl, Key::LAlt, Key::RAlt,
+   Key::F2, Key::F3, Key::F4, Key::F5, Key::F6];
+
+pub fn action_name(action: Action) -> &'static str {
+   match action {
+      Action::Move(Direction::Up) => "up",
+      Action::Move(Direction::Down) => "down",
+      Action::Move(Direction::Left) => "left",
+      Action::Move(Direction::Right) => "right",
+      Action::Snap => "snap",
+      Action::Pause => "pause",
+      Action::RestartLevel => "restart",
+      Action::Suicide => "suicide",
+      Action::Minimap => "minimap"
+   }
+}
+
+fn action_from_name(name: &str) -> Option<Action> {
+   ACTIONS.iter().cloned().find(|&a| action_name(a) == name)
+}
+
+pub fn control_name(control: Control) -> String {
+   match control {
+      Control::Key(k) => format!("{:?}", k),
+      Control::Button(b) => format!("Button{}", b)
+   }
+}
+
+fn control_from_name(name: &str) -> Option<Control> {
+   if name.starts_with("Button") {
+      return name["Button".len()..].parse().ok().map(Control::Button);
+   }
+   BINDABLE_KEYS.iter()
+      .cloned()
+      .map(Control::Key)
+      .find(|&c| control_name(c).eq_ignore_ascii_case(name))
+}
+
+pub fn is_bindable(control: Control) -> bool {
+   match control {
+      Control::Key(k) => BINDABLE_KEYS.contains(&k),
+      Control::Button(_) => true
+   }
+}
+
+pub struct Controls {
+   bindings: Vec<(Action, Vec<Control>)>,
+   actions: HashMap<Control, Action>,
+   pub x_axis: u8,
+   pub y_axis: u8,
+   pub deadzone: f64
+}
+
+impl Default for Controls {
+   fn default() -> Self {
+      Controls::new(vec![
+         (Action::Move(Direction::Up), vec![Control::Key(Key::W), Control::Key(Key::Up), Control::Button(11)]),
+         (Action::Move(Direction::Down), vec![Control::Key(Key::S), Control::Key(Key::Down), Control::Button(12)]),
+         (Action::Move(Direction::Left), vec![Control::Key(Key::A), Control::Key(Key::Left), Control::Button(13)]),
+         (Action::Move(Direction::Right), vec![Control::Key(Key::D), Control::Key(Key::Right), Control::Button(14)]),
+         (Action::Snap, vec![Control::Key(Key::LShift), Control::Key(Key::RShift), Control::Button(0)]),
+         (Action::Pause, vec![Control::Key(Key::P), Control::Button(6)]),
+         (Action::RestartLevel, vec![Control::Key(Key::R)]),
+         (Action::Suicide, vec![Control::Key(Key::K), Control::Button(4)]),
+         (Action::Minimap, vec![Control::Key(Key::M), Control::Button(7)])])
+   }
+}
+
+impl Controls {
+   pub fn new(bindings: Vec<(Action, Vec<Control>)>) -> Controls {
+      let mut actions = HashMap::new();
+      for &(action, ref controls) in &bindings {
+         for &c in controls {
+            actions.insert(c, action);
+         }
+      }
+      Controls { bindings, actions, x_axis: 0, y_axis: 1, deadzone: 0.35 }
+   }
+
+   pub fn action(&self, control: Control) -> Option<Action> {
+      self.actions.get(&control).cloned()
+   }
+
+   pub fn controls(&self, action: Action) -> Vec<Control> {
+      self.bindings.iter()
+         .find(|&&(a, _)| a == action)
+         .map(|&(_, ref keys)| keys.clone())
+         .unwrap_or_default()
+   }
+
+   pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Controls> {
+      let file = BufReader::new(File::open(path)?);
+      let defaults = Controls::default().bindings;
+      let mut bindings = defaults.clone();
+      let mut listed = Vec::new();
+      let (mut x_axis, mut y_axis, mut deadzone) = (0, 1, 0.35);
+      let mut has_gamepad = false;
+      for line in file.lines() {
+         let line = line?;
+         let line = line.trim();
+         if line.is_empty() || line.starts_with('#') {
+            continue;
+         }
+         let mut parts = line.splitn(2, '=');
+         let name = parts.next().unwrap_or("").trim();
+         let value = parts.next().ok_or_else(|| invalid_data(line))?.trim();
+         if name == "x_axis" || name == "y_axis" || name == "deadzone" {
+            has_gamepad = true;
+         }
+         match name {
+            "x_axis" => x_axis = value.parse().map_err(|_| invalid_data(line))?,
+            "y_axis" => y_axis = value.parse().map_err(|_| invalid_data(line))?,
+            "deadzone" => deadzone = value.parse().map_err(|_| invalid_data(line))?,
+            _ => {
+               let action = action_from_name(name).ok_or_else(|| invalid_data(line))?;
+               let binding = bindings.iter_mut().find(|&&mut (a, _)| a == action).unwrap();
+               if !listed.contains(&action) {
+                  listed.push(action);
+                  binding.1.clear();
+               }
+               for c in value.split(',').map(|c| c.trim()).filter(|c| !c.is_empty()) {
+                  binding.1.push(control_from_name(c).ok_or_else(|| invalid_data(line))?);
+               }
+            }
+         }
+      }
+      let taken: Vec<Control> = bindings.iter()
+         .filter(|&&(a, _)| listed.contains(&a))
+         .flat_map(|&(_, ref controls)| controls.clone())
+         .collect();
+      for (&mut (action, ref mut controls), &(_, ref default)) in bindings.iter_mut().zip(&defaults) {
+         if !listed.contains(&action) {
+            controls.retain(|c| !taken.contains(c));
+         } else if !has_gamepad {
+            controls.extend(default.iter().filter(|&c| !taken.contains(c) && if let Control::Button(_) = *c { true } else { false }));
+         }
+      }
+      let mut controls = Controls::new(bindings);
+      controls.x_axis = x_axis;
+      controls.y_axis = y_axis;
+      controls.deadzone = deadzone;
+      Ok(controls)
+   }
+
+   pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+      let mut file = File::create(path)?;
+      for &(action, ref controls) in &self.bindings {
+         let names: Vec<String> = controls.iter().map(|&c| control_name(c)).collect();
+         writeln!(file, "{} = {}", action_name(action), names.join(", "))?;
+      }
+      writeln!(file, "x_axis = {}", self.x_axis)?;
+      writeln!(file, "y_axis = {}", self.y_axis)?;
+      writeln!(file, "deadzone = {}", self.deadzone)?;
+      Ok(())
+   }
+}
+
+fn invalid_data(line: &str) -> io::Error {
+   io::Error::new(io::ErrorKind::InvalidData, format!("Control binding not recognised. {}", line))
+}
+
+pub struct Rebinding {
+   index: usize,
+   pending: Vec<Control>,
+   bindings: Vec<(Action, Vec<Control>)>,
+   axes: (u8, u8, f64)
+}
+
+impl Rebinding {
+   pub fn new(controls: &Controls) -> Rebinding {
+      Rebinding {
+         index: 0,
+         pending: Vec::new(),
+         bindings: controls.bindings.clone(),
+         axes: (controls.x_axis, controls.y_axis, controls.deadzone)
+      }
+   }
+
+   pub fn current(&self) -> Option<Action> {
+      self.bindings.get(self.index).map(|&(a, _)| a)
+   }
+
+   pub fn bindings(&self) -> &[(Action, Vec<Control>)] {
+      &self.bindings
+   }
+
+   pub fn pending(&self) -> &[Control] {
+      &self.pending
+   }
+
+   pub fn press(&mut self, control: Control) {
+      if control == Control::Key(CONFIRM_KEY) {
+         if !self.pending.is_empty() {
+            let pending = self.pending.split_off(0);
+            for &mut (_, ref mut bound) in self.bindings.iter_mut() {
+               bound.retain(|c| !pending.contains(c));
+            }
+            self.bindings[self.index].1 = pending;
+         }
+         self.index += 1;
+      } else if is_bindable(control) && !self.pending.contains(&control) {
+         self.pending.push(control);
+      }
+   }
+
+   pub fn is_done(&self) -> bool {
+      self.index >= self.bindings.len()
+   }
+
+   pub fn finish(self) -> Controls {
+      let mut controls = Controls::new(self.bindings);
+      controls.x_axis = self.axes.0;
+      controls.y_axis = self.axes.1;
+      controls.deadzone = self.axes.2;
+      controls
+   }
+}
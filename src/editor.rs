@@ -0,0 +1,122 @@
+use assets;
+use game::*;
+use mapgen::*;
+use mapping::*;
+use pos::Pos;
+use std::cmp::{max, min};
+use std::io;
+use std::path::Path;
+
+pub const MIN_SIZE: usize = 3;
+pub const MAX_SIZE: usize = 200;
+
+pub struct Editor {
+   pub map: LevelMap,
+   pub source: LevelData,
+   pub path: String,
+   pub tile: Tile,
+   pub playtest: Option<GameState>
+}
+
+impl Editor {
+   pub fn new(level: &LevelData, path: &str) -> Editor {
+      Editor {
+         map: level.to_map(),
+         source: level.clone(),
+         path: path.to_string(),
+         tile: Tile::Wall,
+         playtest: None
+      }
+   }
+
+   pub fn open(path: &str) -> io::Result<Editor> {
+      if Path::new(path).exists() {
+         return Ok(Editor::new(&read_level_file(path)?, path));
+      }
+      let mut map = LevelMap::new(40, 22);
+      put_border_wall(&mut map);
+      Ok(Editor::new(&LevelData::from_map("", &map), path))
+   }
+
+   pub fn palette() -> Vec<Tile> {
+      assets::texture_files().iter().map(|&(t, _)| t).collect()
+   }
+
+   pub fn next_tile(&mut self) {
+      let palette = Editor::palette();
+      let i = palette.iter().position(|&t| t == self.tile).map_or(0, |i| (i + 1) % palette.len());
+      self.tile = palette[i];
+   }
+
+   pub fn paint(&mut self, p: Pos, tile: Tile) {
+      if self.playtest.is_some() || p.x >= self.map.x_len() || p.y >= self.map.y_len() {
+         return;
+      }
+      if tile == Tile::Player {
+         for q in self.map.iter_points() {
+            if self.map[q].tile == Tile::Player {
+               self.map[q].tile = Tile::Empty;
+            }
+         }
+      }
+      self.map[p].tile = tile;
+   }
+
+   pub fn resize(&mut self, dx: i64, dy: i64) {
+      if self.playtest.is_some() {
+         return;
+      }
+      let x_len = min(max(self.map.x_len() as i64 + dx, MIN_SIZE as i64), MAX_SIZE as i64) as usize;
+      let y_len = min(max(self.map.y_len() as i64 + dy, MIN_SIZE as i64), MAX_SIZE as i64) as usize;
+      let mut map = LevelMap::new(x_len, y_len);
+      map.wrap = self.map.wrap;
+      for p in map.iter_points() {
+         if p.x < self.map.x_len() && p.y < self.map.y_len() {
+            map[p].tile = self.map[p].tile;
+         }
+      }
+      self.map = map;
+   }
+
+   pub fn fill_border(&mut self) {
+      if self.playtest.is_some() {
+         return;
+      }
+      put_border_wall(&mut self.map);
+   }
+
+   pub fn toggle_playtest(&mut self) {
+      self.playtest =
+         match self.playtest {
+            Some(_) => None,
+            None if self.map.find_player().is_some() && is_enclosed(&self.map) => Some(GameState::new(self.map.clone(), 0, 1)),
+            None => None
+         };
+   }
+
+   pub fn update(&mut self) {
+      let finished =
+         match self.playtest {
+            Some(ref mut g) => {
+               update_game(g);
+               g.level_complete || !g.player_alive
+            }
+            None => false
+         };
+      if finished {
+         self.playtest = None;
+      }
+   }
+
+   pub fn level(&self) -> LevelData {
+      let edited = LevelData::from_map(&self.source.name, &self.map);
+      LevelData { rows: edited.rows, wrap: edited.wrap, ..self.source.clone() }
+   }
+
+   pub fn save(&self) -> io::Result<()> {
+      if !is_enclosed(&self.map) {
+         return Err(io::Error::new(io::ErrorKind::InvalidData, "Level border is not all wall."));
+      }
+      write_level_file(&self.path, &self.level())
+   }
+}
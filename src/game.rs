@@ -1,34 +1,170 @@
 use mapping::*;
 use pos::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use util::*;
+use mapgen::LevelData;
+use rand;
+use rand::Rng;
 
+pub const TICKS_PER_SECOND: i64 = 120;
+
+#[derive(Clone)]
 pub struct GameState {
    pub map: LevelMap,
    pub player_alive: bool,
    pub player_command: Option<Direction>,
+   pub player_snap: bool,
    pub turn: i64,
    pub score: i64,
    pub crush_resistance: i64,
    pub crush: i64,
    pub level_complete: bool,
-   pub player_lives: i64
+   pub player_lives: i64,
+   pub diamonds: i64,
+   pub diamond_value: i64,
+   pub time_limit: Option<i64>,
+   pub rng: GameRng
 }
 
 impl GameState {
    pub fn new(level_map: LevelMap, score: i64, lives: i64) -> GameState {
+      GameState::with_seed(level_map, score, lives, rand::thread_rng().gen())
+   }
+
+   pub fn with_seed(level_map: LevelMap, score: i64, lives: i64, seed: usize) -> GameState {
       GameState {
          map: level_map,
          player_command: None,
+         player_snap: false,
          player_alive: true,
          turn: 0,
          score: score,
          crush_resistance: 30,
          crush: 30,
          level_complete: false,
-         player_lives: lives
+         player_lives: lives,
+         diamonds: 0,
+         diamond_value: 1,
+         time_limit: None,
+         rng: GameRng::new(seed)
+      }
+   }
+
+   pub fn player_ready(&self) -> bool {
+      match self.map.find_player() {
+         Some(p) => self.map[p].cool_down <= 1,
+         None => false
       }
    }
+
+   pub fn time_left(&self) -> Option<i64> {
+      self.time_limit.map(|limit| ::std::cmp::max(limit - self.turn, 0))
+   }
+}
+
+pub struct Session {
+   pub levels: Vec<LevelData>,
+   pub level: usize,
+   pub start_level: usize,
+   pub lives: i64,
+   pub level_score: i64,
+   pub difficulty: usize,
+   pub tick_rate: i64,
+   pub unlocks: HashMap<usize, Vec<usize>>
+}
+
+impl Session {
+   pub fn new(levels: Vec<LevelData>, start_level: usize, lives: i64) -> Session {
+      Session { levels, level: start_level, start_level, lives, level_score: 0, difficulty: 0, tick_rate: TICKS_PER_SECOND, unlocks: HashMap::new() }
+   }
+
+   pub fn start(&mut self, seed: usize) -> GameState {
+      self.level = self.start_level;
+      self.level_score = 0;
+      self.level_state(0, self.lives, seed)
+   }
+
+   pub fn restart_level(&self, g: &mut GameState) {
+      let seed = g.rng.gen();
+      *g = self.level_state(self.level_score, g.player_lives, seed);
+   }
+
+   pub fn next_level(&self) -> usize {
+      self.unlocks.get(&self.level)
+         .and_then(|next| next.first().cloned())
+         .unwrap_or((self.level + 1) % self.levels.len())
+   }
+
+   pub fn level_state(&self, score: i64, lives: i64, seed: usize) -> GameState {
+      let level = &self.levels[self.level];
+      let mut g = GameState::with_seed(level.to_map_at(self.difficulty), score, lives, seed);
+      g.time_limit = level.difficulty(self.difficulty).time.map(|t| t * self.tick_rate);
+      g.diamond_value = self.difficulty as i64 + 1;
+      g
+   }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Tally {
+   pub level: usize,
+   pub diamonds: i64,
+   pub bonus: i64,
+   pub turn: i64,
+   pub score: i64,
+   pub cave_score: i64
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Progress {
+   Playing,
+   LevelComplete(Tally),
+   LifeLost,
+   LevelSkipped,
+   GameOver { score: i64, level: usize }
+}
+
+pub fn advance_game(g: &mut GameState, session: &mut Session) -> Progress {
+   update_game(g);
+   if g.time_left() == Some(0) && !g.level_complete {
+      g.player_alive = false;
+   }
+   if g.level_complete {
+      let bonus = g.time_left().map_or(0, |t| t / session.tick_rate * g.diamond_value);
+      g.score += bonus;
+      let tally = Tally {
+         level: session.level,
+         diamonds: g.diamonds,
+         bonus,
+         turn: g.turn,
+         score: g.score,
+         cave_score: g.score - session.level_score
+      };
+      let seed = g.rng.gen();
+      session.level = session.next_level();
+      session.level_score = g.score;
+      *g = session.level_state(g.score, g.player_lives, seed);
+      Progress::LevelComplete(tally)
+   } else if !g.player_alive && session.levels[session.level].intermission {
+      let seed = g.rng.gen();
+      session.level = session.next_level();
+      session.level_score = g.score;
+      *g = session.level_state(g.score, g.player_lives, seed);
+      Progress::LevelSkipped
+   } else if !g.player_alive {
+      let seed = g.rng.gen();
+      g.player_lives = g.player_lives - 1;
+      if g.player_lives == 0 {
+         let (score, level) = (g.score, session.level);
+         *g = session.start(seed);
+         Progress::GameOver { score, level }
+      } else {
+         session.level_score = g.score;
+         *g = session.level_state(g.score, g.player_lives, seed);
+         Progress::LifeLost
+      }
+   } else {
+      Progress::Playing
+   }
 }
 
 pub fn update_game(g: &mut GameState) {
@@ -36,6 +172,7 @@ pub fn update_game(g: &mut GameState) {
    let mut touched: HashSet<Pos> = HashSet::new();
    let mut m = &mut g.map;
    let player_command = g.player_command;
+   let player_snap = g.player_snap;
    for p in m.iter_points() {
       m[p].cool_down -= 1;
       if !touched.contains(&p) {
@@ -44,37 +181,29 @@ pub fn update_game(g: &mut GameState) {
          if c.is_cool() {
             match c.tile {
                Tile::Boulder => {
-                  let r = p.down();
+                  let r = m.to(p, Direction::Down);
                   if m[r].tile == Tile::Player {
                      if g.crush == 0 {
                         g.player_alive = false;
-                        m[p].tile = Tile::Empty;
-                        m[r].tile = Tile::Boulder;
-                        m[r].cool_down = 20;
+                        m.move_tile(p, r, Tile::Boulder, 20);
                         touched.insert(r);
                      } else {
                         g.crush -= 1;
                      }
                   } else if m[r].is_empty() {
-                     m[p].tile = Tile::Empty;
-                     m[r].tile = Tile::Boulder;
-                     m[r].cool_down = 20;
+                     m.move_tile(p, r, Tile::Boulder, 20);
                      touched.insert(r);
                   } else {
-                     let d = select_random(Direction::Left, Direction::Right);
-                     let n = p.to(d);
-                     let r = r.to(d);
+                     let d = select_random(&mut g.rng, Direction::Left, Direction::Right);
+                     let n = m.to(p, d);
+                     let r = m.to(r, d);
                      if m[r].is_empty() && m[n].is_empty() {
-                        m[p].tile = Tile::Empty;
-                        m[r].tile = Tile::Boulder;
-                        m[r].cool_down = 20;
+                        m.move_tile(p, r, Tile::Boulder, 20);
                         touched.insert(r);
                      } else if m[r].tile == Tile::Player && m[n].is_empty() {
                         if g.crush == 0 {
                            g.player_alive = false;
-                           m[p].tile = Tile::Empty;
-                           m[r].tile = Tile::Boulder;
-                           m[r].cool_down = 20;
+                           m.move_tile(p, r, Tile::Boulder, 20);
                            touched.insert(r);
                         } else {
                            g.crush -= 1;
@@ -84,50 +213,64 @@ pub fn update_game(g: &mut GameState) {
                }
                Tile::Monster => {
                   if let Some(dir) = m[p].facing {
-                     let r = p.to(dir);
+                     let r = m.to(p, dir);
                      match m[r].tile {
                         Tile::Empty => {
-                           m[p].tile = Tile::Empty;
-                           m[r].tile = Tile::Monster;
                            m[r].facing = m[p].facing;
-                           m[r].cool_down = 7;
+                           m.move_tile(p, r, Tile::Monster, 7);
                            touched.insert(r);
                         }
                         Tile::Player => {
-                           m[p].tile = Tile::Empty;
-                           m[r].tile = Tile::Monster;
                            m[r].facing = m[p].facing;
-                           m[r].cool_down = 7;
+                           m.move_tile(p, r, Tile::Monster, 7);
                            touched.insert(r);
                            g.player_alive = false;
                         }
                         _ => {
-                           m[p].facing = Some(select_random_direction());
+                           m[p].facing = Some(select_random_direction(&mut g.rng));
                            m[p].cool_down = 10;
+                           m[p].moved_from = None;
                         }
                      }
                   } else {
-                     m[p].facing = Some(select_random_direction());
+                     m[p].facing = Some(select_random_direction(&mut g.rng));
                   }
                }
                Tile::Player => {
                   match player_command {
+                     Some(command) if player_snap => {
+                        let r = m.to(p, command);
+                        match m[r].tile {
+                           Tile::Mud => {
+                              m[r].tile = Tile::Empty;
+                              m[p].cool_down = 10;
+                              m[p].moved_from = None;
+                           }
+                           Tile::Diamond => {
+                              m[r].tile = Tile::Empty;
+                              m[p].cool_down = 10;
+                              m[p].moved_from = None;
+                              g.score += g.diamond_value;
+                              g.diamonds += 1;
+                              m.diamond_quota = m.diamond_quota.map(|n| n.saturating_sub(1));
+                           }
+                           _ => {}
+                        }
+                     }
                      Some(command) => {
-                        let r = p.to(command);
+                        let r = m.to(p, command);
                         match m[r].tile {
                            Tile::Empty | Tile::Mud => {
-                              m[p].tile = Tile::Empty;
-                              m[r].tile = Tile::Player;
-                              m[r].cool_down = 10;
+                              m.move_tile(p, r, Tile::Player, 10);
                               touched.insert(r);
                               g.crush = g.crush_resistance;
                            }
                            Tile::Diamond => {
-                              m[p].tile = Tile::Empty;
-                              m[r].tile = Tile::Player;
-                              m[r].cool_down = 10;
+                              m.move_tile(p, r, Tile::Player, 10);
                               touched.insert(r);
-                              g.score += 1;
+                              g.score += g.diamond_value;
+                              g.diamonds += 1;
+                              m.diamond_quota = m.diamond_quota.map(|n| n.saturating_sub(1));
                               g.crush = g.crush_resistance;
                            }
                            Tile::Monster => {
@@ -135,7 +278,7 @@ pub fn update_game(g: &mut GameState) {
                               g.player_alive = false;
                            }
                            Tile::Exit => {
-                              if m.has_no_diamonds() {
+                              if m.exit_open() {
                                  g.level_complete = true;
                               }
                            }
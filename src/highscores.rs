@@ -0,0 +1,107 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const MAX_ENTRIES: usize = 10;
+pub const MAX_NAME_LEN: usize = 12;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HighScore {
+   pub name: String,
+   pub score: i64,
+   pub level: usize,
+   pub date: String
+}
+
+impl HighScore {
+   pub fn new(name: &str, score: i64, level: usize) -> HighScore {
+      let name = name.trim();
+      HighScore {
+         name: if name.is_empty() { "PLAYER".to_string() } else { name.to_string() },
+         score,
+         level,
+         date: today()
+      }
+   }
+}
+
+pub struct HighScores {
+   pub entries: Vec<HighScore>
+}
+
+impl HighScores {
+   pub fn new() -> HighScores {
+      HighScores { entries: Vec::new() }
+   }
+
+   pub fn load<P: AsRef<Path>>(path: P) -> io::Result<HighScores> {
+      let file = match File::open(path) {
+         Ok(file) => file,
+         Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(HighScores::new()),
+         Err(err) => return Err(err)
+      };
+      let mut high_scores = HighScores::new();
+      for line in BufReader::new(file).lines() {
+         if let Some(entry) = parse_entry(&line?) {
+            high_scores.entries.push(entry);
+         }
+      }
+      high_scores.entries.sort_by(|a, b| b.score.cmp(&a.score));
+      high_scores.entries.truncate(MAX_ENTRIES);
+      Ok(high_scores)
+   }
+
+   pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+      let temp_path = path.as_ref().with_extension("tmp");
+      {
+         let mut file = File::create(&temp_path)?;
+         for e in &self.entries {
+            writeln!(file, "{} {} {} {}", e.score, e.level + 1, e.date, e.name)?;
+         }
+      }
+      fs::rename(&temp_path, path)
+   }
+
+   pub fn qualifies(&self, score: i64) -> bool {
+      score > 0 && (self.entries.len() < MAX_ENTRIES || self.entries.iter().any(|e| e.score < score))
+   }
+
+   pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+      if !self.qualifies(entry.score) {
+         return None;
+      }
+      let rank = self.entries.iter().position(|e| e.score < entry.score).unwrap_or(self.entries.len());
+      self.entries.insert(rank, entry);
+      self.entries.truncate(MAX_ENTRIES);
+      Some(rank)
+   }
+}
+
+fn parse_entry(line: &str) -> Option<HighScore> {
+   let mut parts = line.trim().splitn(4, ' ');
+   let score = parts.next()?.parse().ok()?;
+   let level = parts.next()?.parse::<usize>().ok()?.checked_sub(1)?;
+   let date = parts.next()?.to_string();
+   let name = parts.next()?.trim();
+   if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+      return None;
+   }
+   Some(HighScore { name: name.to_string(), score, level, date })
+}
+
+fn today() -> String {
+   let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+   let days = (secs / 86400) as i64 + 719468;
+   let era = days / 146097;
+   let day_of_era = days - era * 146097;
+   let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+   let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+   let mp = (5 * day_of_year + 2) / 153;
+   let day = day_of_year - (153 * mp + 2) / 5 + 1;
+   let month = if mp < 10 { mp + 3 } else { mp - 9 };
+   let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+   format!("{:04}-{:02}-{:02}", year, month, day)
+}
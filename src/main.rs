@@ -4,12 +4,30 @@ mod rect;
 mod pos;
 mod util;
 mod mapgen;
+mod solver;
+mod terminal;
+mod render;
+mod replay;
+mod cli;
+mod controls;
+mod player_input;
+mod screen;
+mod highscores;
+mod savegame;
+mod editor;
+mod pack;
+mod unlocks;
 mod assets;
+mod camera;
+mod sprites;
+mod atlas;
 
 extern crate rand;
 extern crate vecmath;
 extern crate piston_window;
 extern crate find_folder;
+extern crate png;
+extern crate gif;
 
 use std::fs::File;
 use std::path::Path;
@@ -21,164 +39,1059 @@ use vecmath::*;
 use piston_window::*;
 use image::*;
 use mapgen::*;
-use std::collections::HashMap;
 use std::collections::HashSet;
+use rand::Rng;
+use controls::{Action, Control, Controls, Rebinding};
+use player_input::{InputEvent, PlayerInput};
+use screen::{menu_item_name, menu_items, MenuItem, Screen, ScreenEvent};
+use highscores::{HighScore, HighScores};
+use editor::Editor;
+use pack::LevelPack;
+use unlocks::Unlocks;
+use camera::Camera;
+use sprites::Sprites;
+use atlas::Atlas;
+use std::path::PathBuf;
+
+const EDITOR_MARGIN: f64 = 16.0;
+const PALETTE_GAP: f64 = 8.0;
+const LEVEL_SELECT_ROWS: usize = 10;
+const BENCHMARK_FRAMES: usize = 600;
+const BENCHMARK_MAX_FPS: u64 = 10000;
+const HUD_MIN_WIDTH: f64 = 760.0;
+const MINIMAP_SIZE: (f64, f64) = (240.0, 160.0);
+const MINIMAP_MARGIN: f64 = 16.0;
 
 fn main() {
-   let mut old_player_pos = Pos { x: 1, y: 1 };
-   let map_data = vec![level1(), level2(), level3()];
-   let mut level = 0;
-   let texture_files = vec![
-      (Tile::Empty, "empty.png"),
-      (Tile::Player, "man.png"),
-      (Tile::Mud, "mud.png"),
-      (Tile::Wall, "wall.png"),
-      (Tile::Boulder, "boulder.png"),
-      (Tile::Diamond, "diamond.png"),
-      (Tile::Monster, "butterfly.png"),
-      (Tile::Exit, "exit.png")];
+   let args: Vec<String> = std::env::args().skip(1).collect();
+   let options = match cli::Options::parse(&args) {
+      Ok(options) => options,
+      Err(message) => {
+         eprintln!("{}\n\n{}", message, cli::usage());
+         std::process::exit(2);
+      }
+   };
+   if options.help {
+      println!("{}", cli::usage());
+      return;
+   }
+   let mut pack_choices: Vec<Option<String>> = vec![None];
+   pack_choices.extend(pack::find_packs().into_iter().map(|p| Some(p.to_string_lossy().into_owned())));
+   let initial_pack = options.level_file.clone().or(options.pack.clone());
+   if !pack_choices.contains(&initial_pack) {
+      pack_choices.push(initial_pack.clone());
+   }
+   let mut pack_index = pack_choices.iter().position(|c| *c == initial_pack).unwrap();
+   let mut pack = match load_pack(&initial_pack) {
+      Ok(pack) => pack,
+      Err(err) => {
+         eprintln!("Could not load levels: {}", err);
+         std::process::exit(1);
+      }
+   };
+   let levels = pack.levels.clone();
+   if options.level >= levels.len() {
+      eprintln!("Level {} not found.", options.level + 1);
+      std::process::exit(1);
+   }
+   let playback = options.play.as_ref().map(|path| {
+      replay::Replay::load(path).unwrap_or_else(|err| {
+         eprintln!("Could not load replay: {}", err);
+         std::process::exit(1);
+      })
+   });
+   let seed = match playback {
+      Some(ref replay) => replay.seed,
+      None => options.seed.unwrap_or_else(|| rand::thread_rng().gen())
+   };
+   let mut session = Session::new(levels, options.level, options.lives);
+   session.tick_rate = options.tick_rate as i64;
+   session.unlocks = pack.unlocks.clone();
+   session.difficulty = options.difficulty;
+   if let Some(ref path) = options.screenshot {
+      if let Err(err) = render::save_screenshot(&load_level(&session.levels, options.level), &pack.textures, path) {
+         eprintln!("Could not save screenshot: {}", err);
+         std::process::exit(1);
+      }
+      return;
+   }
+   if let Some(ref replay) = playback {
+      if replay.level >= session.levels.len() {
+         eprintln!("Replay level {} not found.", replay.level + 1);
+         std::process::exit(1);
+      }
+      if let Some(ref path) = options.gif {
+         let mut settings = render::GifSettings::new();
+         settings.textures = pack.textures.clone();
+         settings.scale = options.gif_scale;
+         settings.frame_skip = options.gif_frame_skip;
+         settings.view_size = Some((640, 360));
+         if let Err(err) = render::save_replay_gif(replay, &mut session, &settings, path) {
+            eprintln!("Could not save replay animation: {}", err);
+            std::process::exit(1);
+         }
+         return;
+      }
+      if options.headless {
+         let game_state = replay.play_session(&mut session, |_, _| {});
+         println!("level: {}", session.level + 1);
+         println!("score: {}", game_state.score);
+         println!("lives: {}", game_state.player_lives);
+         println!("turn: {}", game_state.turn);
+         print_map(&game_state.map);
+         return;
+      }
+   }
+   if options.terminal {
+      terminal::run(&mut session, seed, options.tick_rate);
+      return;
+   }
+   if let Some(size) = options.benchmark {
+      run_benchmark(&options, &pack, size, seed);
+      return;
+   }
+   let mut camera = Camera::new(options.dead_zone, options.shake);
+   let mut zoom = options.zoom;
+   let mut show_minimap = false;
+   let mut recording = options.record.as_ref().map(|_| replay::Replay::new(options.level, seed, options.lives));
+   let mut playback_tick = 0;
    let mut window: PistonWindow =
-      WindowSettings::new("Boulderdash", [1280, 720])
-         .fullscreen(false)
+      WindowSettings::new("Boulderdash", [options.window_size.0, options.window_size.1])
+         .fullscreen(options.fullscreen)
          .vsync(true)
          .exit_on_esc(true)
          .build()
          .unwrap();
-   let mut input_state = HashSet::<Direction>::new();
+   window.set_ups(options.tick_rate);
+   let mut player_input = PlayerInput::new();
    let mut old_input_state = HashSet::<Direction>::new();
-   let mut textures = std::collections::HashMap::new();
-   for (t, f) in texture_files {
-      let path = assets::find_asset(f);
-      let new_texture =
-         Texture::from_path(
-            &mut window.factory,
-            path,
-            Flip::Vertical,
-            &TextureSettings::new()).unwrap();
-      textures.insert(t, new_texture);
-   }
+   let mut controls = load_controls(&options.controls);
+   let mut rebinding: Option<Rebinding> = None;
+   let mut high_score_path = pack_file_path(&pack.id, "highscores");
+   let mut save_path = pack_file_path(&pack.id, "savegame");
+   let mut has_save = Path::new(&save_path).exists();
+   let mut first_level = options.level;
+   let mut high_scores = load_high_scores(&high_score_path);
+   let mut player_name = String::new();
+   let mut unlocks_path = pack_file_path(&pack.id, "unlocks");
+   let mut unlocks = load_unlocks(&unlocks_path, pack.levels.len());
+   let mut password_entry = String::new();
+   let mut editor =
+      match options.edit {
+         Some(ref path) => {
+            match Editor::open(path) {
+               Ok(editor) => Some(editor),
+               Err(err) => {
+                  eprintln!("Could not open level: {}", err);
+                  std::process::exit(1);
+               }
+            }
+         }
+         None => None
+      };
+   let mut painting: Option<Tile> = None;
+   let mut mouse = (0.0, 0.0);
+   let mut window_size = (options.window_size.0 as f64, options.window_size.1 as f64);
+   let mut screen =
+      if editor.is_some() {
+         Screen::Editor
+      } else if playback.is_some() {
+         Screen::LevelIntro { ticks: 0 }
+      } else {
+         Screen::Title { selection: 0 }
+      };
+   let mut textures = match load_textures(&mut window.factory, &pack) {
+      Ok(textures) => textures,
+      Err(err) => {
+         eprintln!("Could not load textures: {}", err);
+         std::process::exit(1);
+      }
+   };
    let factory = window.factory.clone();
    let font_path = assets::find_asset("font.ttf");
    let mut glyphs = Glyphs::new(font_path, factory).unwrap();
-   let mut game_state = GameState::new(read_map(&map_data[level]), 0, 3);
+   let mut game_state =
+      match playback {
+         Some(ref replay) => replay.start(&mut session),
+         None => session.start(seed)
+      };
    while let Some(e) = window.next() {
-      if let Input::Update(_) = e {
-         update_game(&mut game_state);
-         if game_state.level_complete {
-            level += 1;
-            if level == map_data.len() {
-               level = 0;
-            }
-            game_state = GameState::new(read_map(&map_data[level]), game_state.score, game_state.player_lives);
-         } else if !game_state.player_alive {
-            game_state.player_lives = game_state.player_lives - 1;
-            if game_state.player_lives == 0 {
-               level = 0;
-               game_state = GameState::new(read_map(&map_data[level]), 0, 3);
-            } else {
-               game_state = GameState::new(read_map(&map_data[level]), game_state.score, game_state.player_lives);
+      if let Input::Update(u) = e {
+         if rebinding.is_some() {
+            continue;
+         }
+         if screen == Screen::Playing {
+            if let Some(ref replay) = playback {
+               let (command, snap) = replay.commands.get(playback_tick).cloned().unwrap_or((None, false));
+               game_state.player_command = command;
+               game_state.player_snap = snap;
+               playback_tick += 1;
             }
+            if let Some(ref mut replay) = recording {
+               replay.record(game_state.player_command, game_state.player_snap);
+            }
+         }
+         if let (Screen::Editor, Some(ed)) = (screen, editor.as_mut()) {
+            if let Some(ref mut g) = ed.playtest {
+               g.player_command = player_input.command(&controls);
+               g.player_snap = player_input.snap();
+            }
+            ed.update();
+         }
+         let player_ready = screen != Screen::Playing || game_state.player_ready();
+         let level_before = session.level;
+         screen.update(&mut game_state, &mut session);
+         if screen == Screen::Playing && camera::boulder_impacts(&game_state.map) > 0 {
+            camera.shake(camera::IMPACT_SHAKE);
+         }
+         let tile_size = textures.tile_size(zoom);
+         if !screen.shows_map() {
+            jump_camera(&mut camera, &game_state.map, tile_size, window_size);
+         } else if let Some(focus) = camera_focus(&game_state.map, tile_size) {
+            let map_size = (game_state.map.x_len() as f64 * tile_size.0, game_state.map.y_len() as f64 * tile_size.1);
+            camera.update(focus, map_size, window_size, game_state.map.wrap, u.dt);
+         }
+         if let (Screen::LevelComplete { tally, ticks: 0 }, None) = (screen, playback.as_ref()) {
+            unlocks.complete(tally.level, Some(tally.cave_score), &pack.unlocked_by(tally.level));
+            if let Err(err) = unlocks.save(&unlocks_path) {
+               println!("Could not save cave progress: {}", err);
+            }
+         }
+         if let (Screen::LevelIntro { ticks: 0 }, None) = (screen, playback.as_ref()) {
+            if session.level != level_before {
+               unlocks.complete(level_before, None, &pack.unlocked_by(level_before));
+               if let Err(err) = unlocks.save(&unlocks_path) {
+                  println!("Could not save cave progress: {}", err);
+               }
+            }
+         }
+         if let Screen::GameOver { .. } = screen {
+            if let (Some(replay), Some(path)) = (recording.take(), options.record.as_ref()) {
+               if let Err(err) = replay.save(path) {
+                  println!("Could not save replay: {}", err);
+               }
+            }
+         }
+         if player_ready && playback.is_none() {
+            player_input.consume();
+            game_state.player_command = player_input.command(&controls);
          }
       }
       if let Input::Render(r) = e {
+         window_size = (r.width as f64, r.height as f64);
          window.draw_2d(&e, |context: Context, g2d| {
-            let (cam_x, cam_y) =
-            if let Some(player_pos) = game_state.map.find_player() {
-               old_player_pos = player_pos;
-                  util::calculate_camera_position(
-                     (game_state.map.x_len() as f64 * 16.0, game_state.map.y_len() as f64 * 16.0),
-                     (context.viewport.unwrap().window_size[0] as f64, context.viewport.unwrap().window_size[1] as f64),
-                     (player_pos.x as f64 * 16.0 + 8.0, player_pos.y as f64 * 16.0 + 8.0)
-                  )
-            } else {
-                  util::calculate_camera_position(
-                     (game_state.map.x_len() as f64 * 16.0, game_state.map.y_len() as f64 * 16.0),
-                     (context.viewport.unwrap().window_size[0] as f64, context.viewport.unwrap().window_size[1] as f64),
-                     (old_player_pos.x as f64 * 16.0 + 8.0, old_player_pos.y as f64 * 16.0 + 8.0))
-            };
+            let (cam_x, cam_y) = camera.translation((context.viewport.unwrap().window_size[0] as f64, context.viewport.unwrap().window_size[1] as f64));
             let height = context.viewport.unwrap().window_size[1] as f64;
+            if let (Screen::Editor, Some(ed)) = (screen, editor.as_ref()) {
+               clear([0.0, 0.0, 0.0, 1.0], g2d);
+               draw_editor(&r, g2d, ed, context.trans(0.0, height).scale(1.0, -1.0), &textures, textures.tile_size(zoom), tick_fraction(&r, options.tick_rate), height);
+               return;
+            }
             let context = context.trans(0.0, height);
             let context = context.scale(1.0, -1.0);
             let context = context.trans(cam_x, cam_y);
             let game_context = context.scale(1.0, 1.0);
             clear([0.0, 0.0, 0.0, 1.0], g2d);
-
-            draw_map(&r, g2d, &game_state, game_context, &textures, (16.0, 16.0));
+            if !screen.shows_map() {
+               return;
+            }
+            let view = [-cam_x, -cam_y, context.viewport.unwrap().window_size[0] as f64, height];
+            let frame = if screen == Screen::Playing { tick_fraction(&r, options.tick_rate) } else { 0.0 };
+            draw_map(g2d, &game_state.map, game_context, &textures, textures.tile_size(zoom), game_state.turn, frame, view);
          });
          window.draw_2d(&e, |context, g2d| {
-            let text_color = [0.0, 1.0, 1.0, 1.0];
-            let text_context = context.trans(context.viewport.unwrap().window_size[0] as f64 - 300.0, 30.0);
-            let score_string = std::fmt::format(format_args!("SCORE: {} ", game_state.score));
-            text::Text::new_color(text_color, 32).draw(
-               &score_string,
-               &mut glyphs,
-               &text_context.draw_state,
-               text_context.transform,
-               g2d);
-            let text_color = [0.0, 1.0, 1.0, 1.0];
-            let text_context = context.trans(context.viewport.unwrap().window_size[0] as f64 - 500.0, 30.0);
-            let lives_string = std::fmt::format(format_args!("LIVES: {} ", game_state.player_lives));
-            text::Text::new_color(text_color, 32).draw(
-               &lives_string,
-               &mut glyphs,
-               &text_context.draw_state,
-               text_context.transform,
-               g2d);
+            match rebinding {
+               Some(ref r) => draw_rebinding(g2d, &mut glyphs, context, r),
+               None => draw_screen(g2d, &mut glyphs, context, &screen, &session, &game_state, &menu_items(has_save && recording.is_none()), &pack, &high_scores, &unlocks, &player_name, &password_entry)
+            }
+            if let (Screen::Editor, Some(ed)) = (screen, editor.as_ref()) {
+               draw_editor_hud(g2d, &mut glyphs, context, ed);
+            }
+            if !screen.shows_map() {
+               return;
+            }
+            let width = context.viewport.unwrap().window_size[0] as f64;
+            let mut hud = vec![format!("SCORE: {}", game_state.score), format!("LIVES: {}", game_state.player_lives)];
+            if let Some(t) = game_state.time_left() {
+               hud.push(format!("TIME: {}", t / session.tick_rate));
+            }
+            for (i, item) in hud.iter().enumerate() {
+               draw_text(g2d, &mut glyphs, context, item, hud_position(i, width), 32);
+            }
+            if show_minimap {
+               let tile_size = textures.tile_size(zoom);
+               let view = camera.view(window_size);
+               let cells = [view[0] / tile_size.0, view[1] / tile_size.1, view[2] / tile_size.0, view[3] / tile_size.1];
+               draw_minimap(g2d, context, &game_state.map, cells, window_size, game_state.turn);
+            }
          });
       }
-      if let Input::Press(b) = e {
-         if let Some(direction) = translate_input(b) {
-            input_state.insert(direction);
-            game_state.player_command = Some(direction);
+      if let Input::Resize(w, h) = e {
+         window_size = (w as f64, h as f64);
+         jump_camera(&mut camera, &game_state.map, textures.tile_size(zoom), window_size);
+      }
+      if let Input::Press(Button::Keyboard(key)) = e {
+         let new_zoom =
+            match key {
+               k if k == controls::ZOOM_IN_KEY => std::cmp::min(zoom + 1, cli::MAX_ZOOM),
+               k if k == controls::ZOOM_OUT_KEY => std::cmp::max(zoom - 1, 1),
+               _ => zoom
+            };
+         if new_zoom != zoom {
+            zoom = new_zoom;
+            jump_camera(&mut camera, &game_state.map, textures.tile_size(zoom), window_size);
+         }
+      }
+      if let Input::Press(Button::Keyboard(Key::F12)) = e {
+         let shown =
+            match (screen, editor.as_ref()) {
+               (Screen::Editor, Some(ed)) => Some(ed.playtest.as_ref().map_or(&ed.map, |g| &g.map)),
+               _ if screen.shows_map() => Some(&game_state.map),
+               _ => None
+            };
+         if let Some(m) = shown {
+            let path = format!("screenshot_{}.png", std::time::SystemTime::now()
+               .duration_since(std::time::UNIX_EPOCH).unwrap().as_secs());
+            match render::save_screenshot(m, &pack.textures, &path) {
+               Ok(()) => println!("Saved {}", path),
+               Err(err) => println!("Screenshot failed: {}", err)
+            }
+         }
+      }
+      if let Input::Press(Button::Keyboard(key)) = e {
+         let can_save = screen.shows_map() && recording.is_none() && playback.is_none() && rebinding.is_none();
+         if key == controls::QUICK_SAVE_KEY && can_save {
+            match savegame::save_game(&save_path, &game_state, &session) {
+               Ok(()) => {
+                  has_save = true;
+                  println!("Saved {}", save_path);
+               }
+               Err(err) => println!("Could not save game: {}", err)
+            }
+         }
+         if key == controls::QUICK_LOAD_KEY && can_save {
+            match savegame::load_game(&save_path, &mut session) {
+               Ok(g) => {
+                  game_state = g;
+                  player_input.clear();
+               }
+               Err(err) => println!("Could not load game: {}", err)
+            }
+         }
+      }
+      if let Input::Move(Motion::MouseCursor(x, y)) = e {
+         mouse = (x, y);
+      }
+      if screen == Screen::Editor && rebinding.is_none() {
+         let mut leave = false;
+         if let Some(ref mut ed) = editor {
+            match e {
+               Input::Press(Button::Mouse(MouseButton::Left)) => {
+                  match palette_tile(mouse, textures.tile_size(zoom)) {
+                     Some(tile) => ed.tile = tile,
+                     None => painting = Some(ed.tile)
+                  }
+               }
+               Input::Press(Button::Mouse(MouseButton::Right)) => {
+                  painting = Some(Tile::Empty);
+               }
+               Input::Release(Button::Mouse(_)) => {
+                  painting = None;
+               }
+               Input::Press(Button::Keyboard(key)) => {
+                  match key {
+                     Key::Tab => ed.next_tile(),
+                     Key::LeftBracket => ed.resize(-1, 0),
+                     Key::RightBracket => ed.resize(1, 0),
+                     Key::Minus => ed.resize(0, -1),
+                     Key::Equals => ed.resize(0, 1),
+                     Key::B => ed.fill_border(),
+                     Key::T => {
+                        ed.toggle_playtest();
+                        player_input.clear();
+                     }
+                     Key::F7 => {
+                        match ed.save() {
+                           Ok(()) => println!("Saved {}", ed.path),
+                           Err(err) => println!("Could not save level: {}", err)
+                        }
+                     }
+                     Key::Backspace if ed.playtest.is_none() => leave = true,
+                     _ => {}
+                  }
+               }
+               _ => {}
+            }
+            if let Some(tile) = painting {
+               if let Some(p) = editor_cell(ed, mouse, window_size.1, textures.tile_size(zoom)) {
+                  ed.paint(p, tile);
+               }
+            }
+         }
+         if leave {
+            editor = None;
+            painting = None;
+            screen = Screen::Title { selection: 0 };
          }
       }
-      if let Input::Release(b) = e {
-         if let Some(direction) = translate_input(b) {
-            input_state.remove(&direction);
-            game_state.player_command = None;
-            let ds = vec![pos::Direction::Up, Direction::Left, Direction::Right, Direction::Down];
-            for d in ds {
-               if input_state.contains(&d) {
-                  game_state.player_command = Some(d);
+      let entry =
+         match screen {
+            Screen::EnterName { .. } => Some((&mut player_name, highscores::MAX_NAME_LEN)),
+            Screen::EnterPassword => Some((&mut password_entry, unlocks::PASSWORD_LEN)),
+            _ => None
+         };
+      if let Some((entry, max_len)) = entry {
+         match e {
+            Input::Text(ref text) => {
+               for c in text.chars().filter(|c| c.is_alphanumeric() || *c == ' ') {
+                  if entry.chars().count() < max_len {
+                     entry.push(c.to_ascii_uppercase());
+                  }
                }
             }
+            Input::Press(Button::Keyboard(Key::Backspace)) => {
+               entry.pop();
+            }
+            _ => {}
+         }
+      }
+      if let Some(event) = translate_event(&e) {
+         if let InputEvent::Press(control) = event {
+            if let Some(mut r) = rebinding.take() {
+               r.press(control);
+               if r.is_done() {
+                  controls = r.finish();
+                  if let Err(err) = controls.save(&options.controls) {
+                     println!("Could not save controls: {}", err);
+                  }
+               } else {
+                  rebinding = Some(r);
+               }
+               continue;
+            }
+            if control == Control::Key(controls::MENU_KEY) {
+               rebinding = Some(Rebinding::new(&controls));
+               player_input.clear();
+               game_state.player_command = None;
+               game_state.player_snap = false;
+               continue;
+            }
+         }
+         let action = player_input.handle(&controls, event);
+         if let InputEvent::Press(control) = event {
+            match screen.press(action, control, &menu_items(has_save && recording.is_none()), &high_scores, &unlocks) {
+               Some(ScreenEvent::Selected(MenuItem::Start)) => {
+                  session.start_level = first_level;
+                  game_state = start_session(&mut session, &mut recording, options.seed);
+                  player_input.clear();
+               }
+               Some(ScreenEvent::CaveChosen(level)) => {
+                  session.start_level = level;
+                  game_state = start_session(&mut session, &mut recording, options.seed);
+                  player_input.clear();
+               }
+               Some(ScreenEvent::PasswordEntered) => {
+                  if password_entry.is_empty() {
+                     screen = Screen::Title { selection: 0 };
+                  } else {
+                     match unlocks::level_for_password(&pack.id, session.levels.len(), &password_entry) {
+                        Some(level) => {
+                           unlocks.unlock(level);
+                           if let Err(err) = unlocks.save(&unlocks_path) {
+                              println!("Could not save cave progress: {}", err);
+                           }
+                           screen = Screen::LevelSelect { selection: level };
+                        }
+                        None => println!("Password not recognised. {}", password_entry)
+                     }
+                  }
+                  password_entry.clear();
+               }
+               Some(ScreenEvent::Selected(MenuItem::Difficulty)) => {
+                  session.difficulty = (session.difficulty + 1) % DIFFICULTIES;
+               }
+               Some(ScreenEvent::Selected(MenuItem::Continue)) if recording.is_none() => {
+                  match savegame::load_game(&save_path, &mut session) {
+                     Ok(g) => {
+                        game_state = g;
+                        screen = Screen::LevelIntro { ticks: 0 };
+                     }
+                     Err(err) => println!("Could not load game: {}", err)
+                  }
+               }
+               Some(ScreenEvent::Selected(MenuItem::Pack)) if recording.is_none() => {
+                  let next = (pack_index + 1) % pack_choices.len();
+                  match load_pack(&pack_choices[next]).and_then(|p| {
+                     load_textures(&mut window.factory, &p)
+                        .map(|t| (p, t))
+                        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+                  }) {
+                     Ok((p, t)) => {
+                        pack_index = next;
+                        pack = p;
+                        textures = t;
+                        let difficulty = session.difficulty;
+                        first_level = 0;
+                        session = Session::new(pack.levels.clone(), first_level, options.lives);
+                        session.tick_rate = options.tick_rate as i64;
+                        session.unlocks = pack.unlocks.clone();
+                        session.difficulty = difficulty;
+                        game_state = session.start(rand::thread_rng().gen());
+                        high_score_path = pack_file_path(&pack.id, "highscores");
+                        save_path = pack_file_path(&pack.id, "savegame");
+                        has_save = Path::new(&save_path).exists();
+                        high_scores = load_high_scores(&high_score_path);
+                        unlocks_path = pack_file_path(&pack.id, "unlocks");
+                        unlocks = load_unlocks(&unlocks_path, pack.levels.len());
+                     }
+                     Err(err) => println!("Could not load level pack: {}", err)
+                  }
+               }
+               Some(ScreenEvent::Selected(MenuItem::Editor)) => {
+                  let path = options.edit.clone().unwrap_or_else(|| "custom_level.txt".to_string());
+                  editor = Some(Editor::new(&session.levels[session.start_level], &path));
+               }
+               Some(ScreenEvent::Selected(MenuItem::Controls)) => {
+                  rebinding = Some(Rebinding::new(&controls));
+               }
+               Some(ScreenEvent::Selected(MenuItem::Quit)) => {
+                  window.set_should_close(true);
+               }
+               Some(ScreenEvent::NameEntered { score, level }) => {
+                  let highlight = high_scores.insert(HighScore::new(&player_name, score, level));
+                  if let Err(err) = high_scores.save(&high_score_path) {
+                     println!("Could not save high scores: {}", err);
+                  }
+                  screen = Screen::HighScores { highlight };
+               }
+               _ => {}
+            }
+         }
+         match (action, playback.as_ref()) {
+            (Some(Action::RestartLevel), None) if recording.is_none() && screen == Screen::Playing => {
+               session.restart_level(&mut game_state);
+            }
+            (Some(Action::Suicide), None) if recording.is_none() && screen == Screen::Playing => {
+               game_state.player_alive = false;
+            }
+            (Some(Action::Minimap), _) if screen.shows_map() => {
+               show_minimap = !show_minimap;
+            }
+            _ => {}
+         }
+         if playback.is_none() {
+            game_state.player_command = player_input.command(&controls);
+            game_state.player_snap = player_input.snap();
          }
       }
       if let Input::Close(_) = e {
          window.set_should_close(true);
       }
    }
+   if let (Some(replay), Some(path)) = (recording, options.record) {
+      if let Err(err) = replay.save(&path) {
+         eprintln!("Could not save replay: {}", err);
+         std::process::exit(1);
+      }
+   }
 }
 
-fn draw_map(r: &RenderArgs, g2d: &mut G2d, game_state:
-&GameState, context: Context, textures: &std::collections::HashMap<Tile, G2dTexture>, tile_size: (f64, f64)) {
-   let m = &game_state.map;
-   for (i, c) in m {
-      let x = (i.x as f64) * tile_size.0;
-      let y = (i.y as f64) * tile_size.1;
-      let transform = context.trans(x, y).transform;
-      let mut tile = c.tile;
-      if c.tile == Tile::Exit && !m.has_no_diamonds() {
-         tile = Tile::Wall;
+fn load_controls(path: &str) -> Controls {
+   if !Path::new(path).exists() {
+      return Controls::default();
+   }
+   match Controls::load(path) {
+      Ok(controls) => controls,
+      Err(err) => {
+         println!("Could not load controls, using defaults: {}", err);
+         Controls::default()
       }
-      let texture = textures.get(&tile).unwrap();
-      image(texture, transform, g2d);
    }
 }
 
-fn translate_input(b: Button) -> Option<Direction> {
-   let key_map: std::collections::HashMap<Key, Direction> = [
-      (Key::W, Direction::Up),
-      (Key::S, Direction::Down),
-      (Key::A, Direction::Left),
-      (Key::D, Direction::Right)].iter().cloned().collect();
+fn pack_file_path(pack_id: &str, prefix: &str) -> String {
+   if pack_id.is_empty() {
+      format!("{}.txt", prefix)
+   } else {
+      format!("{}_{}.txt", prefix, pack_id)
+   }
+}
 
-   match b {
-      Button::Keyboard(k) => {
-         key_map.get(&k).cloned()
+fn load_high_scores(path: &str) -> HighScores {
+   HighScores::load(path).unwrap_or_else(|err| {
+      println!("Could not load high scores: {}", err);
+      HighScores::new()
+   })
+}
+
+fn load_unlocks(path: &str, count: usize) -> Unlocks {
+   Unlocks::load(path, count).unwrap_or_else(|err| {
+      println!("Could not load cave progress: {}", err);
+      Unlocks::new(count)
+   })
+}
+
+fn start_session(session: &mut Session, recording: &mut Option<replay::Replay>, seed: Option<usize>) -> GameState {
+   let seed =
+      match *recording {
+         Some(ref mut replay) => {
+            replay.level = session.start_level;
+            replay.difficulty = session.difficulty;
+            replay.tick_rate = session.tick_rate;
+            replay.seed
+         }
+         None => seed.unwrap_or_else(|| rand::thread_rng().gen())
+      };
+   session.start(seed)
+}
+
+fn load_pack(choice: &Option<String>) -> std::io::Result<LevelPack> {
+   match *choice {
+      Some(ref path) => LevelPack::load(path),
+      None => Ok(LevelPack::builtin())
+   }
+}
+
+struct TileTextures {
+   atlas: G2dTexture,
+   base_size: (f64, f64),
+   rects: Atlas,
+   animated: [bool; atlas::TILE_KINDS],
+   sprites: Sprites
+}
+
+impl TileTextures {
+   fn tile_size(&self, zoom: u32) -> (f64, f64) {
+      (self.base_size.0 * zoom as f64, self.base_size.1 * zoom as f64)
+   }
+
+   fn sprite(&self, m: &LevelMap, p: Pos, tile: Tile, time: i64) -> [f64; 4] {
+      if self.animated[tile as usize] {
+         if let Some(animation) = self.sprites.find(tile, sprites::anim_state(m, p)) {
+            if let Some(rect) = self.rects.frame_rect(animation, animation.frame(time, p)) {
+               return rect;
+            }
+         }
+      }
+      self.rects.tile_rect(tile)
+   }
+}
+
+fn load_textures(factory: &mut GfxFactory, pack: &LevelPack) -> Result<TileTextures, String> {
+   let rects = Atlas::build(&pack.textures, &pack.sprites.sheets()).map_err(|err| err.to_string())?;
+   let (width, height) = (rects.image.width, rects.image.height);
+   let mut pixels = Vec::with_capacity(rects.image.data.len());
+   for row in rects.image.data.chunks(width * 4).rev() {
+      pixels.extend_from_slice(row);
+   }
+   let atlas = Texture::create(factory, Format::Rgba8, &pixels, [width as u32, height as u32], &TextureSettings::new().filter(Filter::Nearest))
+      .map_err(|err| format!("{:?}", err));
+   let mut animated = [false; atlas::TILE_KINDS];
+   for &(t, _, _) in &pack.sprites.animations {
+      animated[t as usize] = true;
+   }
+   let base_size = (rects.tile_size.0 as f64, rects.tile_size.1 as f64);
+   Ok(TileTextures { atlas: atlas?, base_size, rects, animated, sprites: pack.sprites.clone() })
+}
+
+fn run_benchmark(options: &cli::Options, pack: &LevelPack, size: (u32, u32), seed: usize) {
+   let cave = generate_cave(&CaveSettings::new(size.0 as usize, size.1 as usize), seed);
+   let mut game_state = GameState::with_seed(cave.map, 0, 1, seed);
+   let mut window: PistonWindow =
+      WindowSettings::new("Boulderdash benchmark", [options.window_size.0, options.window_size.1])
+         .vsync(false)
+         .exit_on_esc(true)
+         .build()
+         .unwrap();
+   window.set_ups(options.tick_rate);
+   window.set_max_fps(BENCHMARK_MAX_FPS);
+   let textures = match load_textures(&mut window.factory, pack) {
+      Ok(textures) => textures,
+      Err(err) => {
+         eprintln!("Could not load textures: {}", err);
+         std::process::exit(1);
+      }
+   };
+   let tile_size = textures.tile_size(options.zoom);
+   let map_size = (size.0 as f64 * tile_size.0, size.1 as f64 * tile_size.1);
+   let mut camera = Camera::new((0.0, 0.0), false);
+   let mut window_size = (options.window_size.0 as f64, options.window_size.1 as f64);
+   let mut elapsed = 0.0;
+   let mut culled_times = Vec::new();
+   let mut full_times = Vec::new();
+   let mut visible_cells = 0;
+   while let Some(e) = window.next() {
+      if let Input::Update(u) = e {
+         update_game(&mut game_state);
+         elapsed += u.dt;
+         let focus = (map_size.0 * (0.5 + 0.5 * (elapsed * 0.3).sin()), map_size.1 * (0.5 + 0.5 * (elapsed * 0.2).cos()));
+         camera.update(focus, map_size, window_size, false, u.dt);
+      }
+      if let Input::Render(r) = e {
+         window_size = (r.width as f64, r.height as f64);
+         let culled = culled_times.len() < BENCHMARK_FRAMES;
+         let (cam_x, cam_y) = camera.translation(window_size);
+         let view =
+            if culled {
+               [-cam_x, -cam_y, window_size.0, window_size.1]
+            } else {
+               [0.0, 0.0, map_size.0, map_size.1]
+            };
+         if culled {
+            let (x0, x1) = visible_range(view[0], view[2], tile_size.0, game_state.map.x_len(), false);
+            let (y0, y1) = visible_range(view[1], view[3], tile_size.1, game_state.map.y_len(), false);
+            visible_cells += ((x1 - x0) * (y1 - y0)) as usize;
+         }
+         let start = std::time::Instant::now();
+         window.draw_2d(&e, |context: Context, g2d| {
+            let context = context.trans(0.0, window_size.1).scale(1.0, -1.0).trans(cam_x, cam_y);
+            clear([0.0, 0.0, 0.0, 1.0], g2d);
+            draw_map(g2d, &game_state.map, context, &textures, tile_size, game_state.turn, tick_fraction(&r, options.tick_rate), view);
+         });
+         let duration = start.elapsed();
+         if culled {
+            culled_times.push(duration);
+         } else {
+            full_times.push(duration);
+         }
+         if full_times.len() >= BENCHMARK_FRAMES {
+            break;
+         }
+      }
+   }
+   if culled_times.is_empty() {
+      return;
+   }
+   println!("cave: {}x{}", size.0, size.1);
+   println!("cells: {} total, {} visible on average", size.0 * size.1, visible_cells / culled_times.len());
+   print_frame_times("culled", &culled_times);
+   print_frame_times("full map", &full_times);
+}
+
+fn print_frame_times(label: &str, times: &[std::time::Duration]) {
+   if times.is_empty() {
+      return;
+   }
+   let mut ms: Vec<f64> = times.iter().map(|d| d.as_secs() as f64 * 1000.0 + d.subsec_nanos() as f64 / 1.0e6).collect();
+   ms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+   println!("{} draws: {}", label, ms.len());
+   println!("{} mean: {:.3} ms", label, ms.iter().sum::<f64>() / ms.len() as f64);
+   println!("{} median: {:.3} ms", label, ms[ms.len() / 2]);
+   println!("{} 95th percentile: {:.3} ms", label, ms[ms.len() * 95 / 100]);
+   println!("{} worst: {:.3} ms", label, ms[ms.len() - 1]);
+}
+
+fn camera_focus(m: &LevelMap, tile_size: (f64, f64)) -> Option<(f64, f64)> {
+   let p = m.find_player()?;
+   let offset = m.move_offset(p, 0.0).unwrap_or((0.0, 0.0));
+   Some(((p.x as f64 + offset.0 + 0.5) * tile_size.0, (p.y as f64 + offset.1 + 0.5) * tile_size.1))
+}
+
+fn jump_camera(camera: &mut Camera, m: &LevelMap, tile_size: (f64, f64), view_size: (f64, f64)) {
+   if let Some(focus) = camera_focus(m, tile_size) {
+      let map_size = (m.x_len() as f64 * tile_size.0, m.y_len() as f64 * tile_size.1);
+      camera.jump(focus, map_size, view_size, m.wrap);
+   }
+}
+
+fn draw_map(g2d: &mut G2d, m: &LevelMap, context: Context, textures: &TileTextures, tile_size: (f64, f64), time: i64, frame: f64, view: [f64; 4]) {
+   let (x0, x1) = visible_range(view[0], view[2], tile_size.0, m.x_len(), m.wrap);
+   let (y0, y1) = visible_range(view[1], view[3], tile_size.1, m.y_len(), m.wrap);
+   let mut moving = Vec::new();
+   for vy in y0..y1 {
+      for vx in x0..x1 {
+         let i = Pos { x: vx.rem_euclid(m.x_len() as i64) as usize, y: vy.rem_euclid(m.y_len() as i64) as usize };
+         let c = m[i];
+         let mut tile = c.tile;
+         if c.tile == Tile::Exit && !m.exit_open() {
+            tile = Tile::Wall;
+         }
+         let at = (vx as f64, vy as f64);
+         let offset = if c.is_empty() { None } else { m.move_offset(i, frame) };
+         if let Some(offset) = offset {
+            moving.push((i, tile, (at.0 + offset.0, at.1 + offset.1)));
+            tile = Tile::Empty;
+         }
+         draw_tile(g2d, context, textures, textures.sprite(m, i, tile, time), at, tile_size);
+      }
+   }
+   for (i, tile, at) in moving {
+      draw_tile(g2d, context, textures, textures.sprite(m, i, tile, time), at, tile_size);
+   }
+}
+
+fn tick_fraction(r: &RenderArgs, tick_rate: u64) -> f64 {
+   (r.ext_dt * tick_rate as f64).max(0.0).min(1.0)
+}
+
+fn visible_range(start: f64, len: f64, tile_len: f64, map_len: usize, wrap: bool) -> (i64, i64) {
+   let first = (start / tile_len).floor() as i64 - 1;
+   let last = ((start + len) / tile_len).ceil() as i64 + 1;
+   if wrap {
+      (first, last)
+   } else {
+      (std::cmp::max(first, 0), std::cmp::min(last, map_len as i64))
+   }
+}
+
+fn draw_tile(g2d: &mut G2d, context: Context, textures: &TileTextures, src_rect: [f64; 4], at: (f64, f64), tile_size: (f64, f64)) {
+   let transform = context.trans((at.0 * tile_size.0).round(), (at.1 * tile_size.1).round()).transform;
+   Image::new()
+      .src_rect(src_rect)
+      .rect([0.0, 0.0, tile_size.0, tile_size.1])
+      .draw(&textures.atlas, &context.draw_state, transform, g2d);
+}
+
+fn draw_editor(r: &RenderArgs, g2d: &mut G2d, editor: &Editor, context: Context, textures: &TileTextures, tile_size: (f64, f64), frame: f64, height: f64) {
+   let (m, time, frame) =
+      match editor.playtest {
+         Some(ref g) => (&g.map, g.turn, frame),
+         None => (&editor.map, 0, 0.0)
+      };
+   draw_map(g2d, m, context.trans(EDITOR_MARGIN, EDITOR_MARGIN), textures, tile_size, time, frame, [-EDITOR_MARGIN, -EDITOR_MARGIN, r.width as f64, height]);
+   for (i, tile) in Editor::palette().into_iter().enumerate() {
+      let x = EDITOR_MARGIN + i as f64 * (tile_size.0 + PALETTE_GAP);
+      let y = height - EDITOR_MARGIN - tile_size.1;
+      if tile == editor.tile {
+         rectangle([1.0, 1.0, 0.0, 1.0], [x - 2.0, y - 2.0, tile_size.0 + 4.0, tile_size.1 + 4.0], context.transform, g2d);
       }
-      _ => {
-         None
+      draw_tile(g2d, context.trans(x, y), textures, textures.rects.tile_rect(tile), (0.0, 0.0), tile_size);
+   }
+}
+
+fn draw_editor_hud(g2d: &mut G2d, glyphs: &mut Glyphs, context: Context, editor: &Editor) {
+   let status = format!("{}  {}x{}  TILE: {}{}",
+      editor.path,
+      editor.map.x_len(),
+      editor.map.y_len(),
+      format!("{:?}", editor.tile).to_uppercase(),
+      if editor.playtest.is_some() { "  PLAY-TEST" } else if !is_enclosed(&editor.map) { "  BORDER OPEN" } else { "" });
+   draw_text(g2d, glyphs, context, &status, (EDITOR_MARGIN, 70.0), 20);
+   draw_text(g2d, glyphs, context, "TAB tile  [ ] width  - = height  B border  T play-test  F7 save  BACKSPACE exit", (EDITOR_MARGIN, 100.0), 20);
+}
+
+fn palette_tile(mouse: (f64, f64), tile_size: (f64, f64)) -> Option<Tile> {
+   let (x, y) = (mouse.0 - EDITOR_MARGIN, mouse.1 - EDITOR_MARGIN);
+   let spacing = tile_size.0 + PALETTE_GAP;
+   if y < 0.0 || y >= tile_size.1 || x < 0.0 || x % spacing >= tile_size.0 {
+      return None;
+   }
+   Editor::palette().get((x / spacing) as usize).cloned()
+}
+
+fn editor_cell(editor: &Editor, mouse: (f64, f64), height: f64, tile_size: (f64, f64)) -> Option<Pos> {
+   let x = ((mouse.0 - EDITOR_MARGIN) / tile_size.0).floor();
+   let y = ((height - mouse.1 - EDITOR_MARGIN) / tile_size.1).floor();
+   if x < 0.0 || y < 0.0 || x >= editor.map.x_len() as f64 || y >= editor.map.y_len() as f64 {
+      return None;
+   }
+   Some(Pos { x: x as usize, y: y as usize })
+}
+
+fn translate_button(b: Button) -> Option<Control> {
+   match b {
+      Button::Keyboard(k) => Some(Control::Key(k)),
+      Button::Controller(c) => Some(Control::Button(c.button)),
+      _ => None
+   }
+}
+
+fn translate_event(e: &Input) -> Option<InputEvent> {
+   match *e {
+      Input::Press(b) => translate_button(b).map(InputEvent::Press),
+      Input::Release(b) => translate_button(b).map(InputEvent::Release),
+      Input::Move(Motion::ControllerAxis(axis)) => Some(InputEvent::Axis(axis.axis, axis.position)),
+      _ => None
+   }
+}
+
+fn minimap_color(tile: Tile) -> [f32; 4] {
+   match tile {
+      Tile::Player => [1.0, 1.0, 1.0, 1.0],
+      Tile::Wall => [0.55, 0.55, 0.6, 1.0],
+      Tile::Boulder => [0.6, 0.4, 0.2, 1.0],
+      Tile::Monster => [1.0, 0.2, 0.8, 1.0],
+      Tile::Diamond => [0.3, 0.9, 1.0, 1.0],
+      Tile::Exit => [0.2, 1.0, 0.2, 1.0],
+      Tile::Mud => [0.35, 0.22, 0.1, 1.0],
+      Tile::Empty => [0.0, 0.0, 0.0, 1.0]
+   }
+}
+
+fn draw_minimap(g2d: &mut G2d, context: Context, m: &LevelMap, view: [f64; 4], window_size: (f64, f64), time: i64) {
+   let block = (MINIMAP_SIZE.0 / m.x_len() as f64).min(MINIMAP_SIZE.1 / m.y_len() as f64).floor().max(1.0);
+   let size = (m.x_len() as f64 * block, m.y_len() as f64 * block);
+   let origin = (window_size.0 - MINIMAP_MARGIN - size.0, window_size.1 - MINIMAP_MARGIN - size.1);
+   let top = |y: usize| origin.1 + (m.y_max() - y) as f64 * block;
+   rectangle([0.0, 0.0, 0.0, 0.8], [origin.0 - 2.0, origin.1 - 2.0, size.0 + 4.0, size.1 + 4.0], context.transform, g2d);
+   for y in 0..m.y_len() {
+      let mut x = 0;
+      while x < m.x_len() {
+         let tile = render::display_tile(m, m.get(x, y));
+         let mut run = 1;
+         while x + run < m.x_len() && render::display_tile(m, m.get(x + run, y)) == tile {
+            run += 1;
+         }
+         if tile != Tile::Empty {
+            rectangle(minimap_color(tile), [origin.0 + x as f64 * block, top(y), run as f64 * block, block], context.transform, g2d);
+         }
+         x += run;
       }
    }
+   let flash = (time / (TICKS_PER_SECOND / 4)) % 2 == 0;
+   let mut highlights = vec![];
+   if let Some(p) = m.find_player() {
+      highlights.push((p, minimap_color(Tile::Player)));
+   }
+   if m.exit_open() && flash {
+      highlights.extend(m.iter_points().filter(|&p| m[p].tile == Tile::Exit).map(|p| (p, minimap_color(Tile::Exit))));
+   }
+   for (p, color) in highlights {
+      let mark = block * 3.0;
+      let x = origin.0 + p.x as f64 * block + block / 2.0 - mark / 2.0;
+      let y = top(p.y) + block / 2.0 - mark / 2.0;
+      rectangle(color, [x, y, mark, mark], context.transform, g2d);
+   }
+   let x0 = (origin.0 + view[0] * block).max(origin.0);
+   let x1 = (origin.0 + (view[0] + view[2]) * block).min(origin.0 + size.0);
+   let y0 = (origin.1 + (m.y_len() as f64 - view[1] - view[3]) * block).max(origin.1);
+   let y1 = (origin.1 + (m.y_len() as f64 - view[1]) * block).min(origin.1 + size.1);
+   if x1 > x0 && y1 > y0 {
+      Rectangle::new_border([1.0, 1.0, 0.0, 1.0], 1.0).draw([x0, y0, x1 - x0, y1 - y0], &context.draw_state, context.transform, g2d);
+   }
+}
+
+fn hud_position(index: usize, width: f64) -> (f64, f64) {
+   if width < HUD_MIN_WIDTH {
+      (10.0, 30.0 + 40.0 * index as f64)
+   } else {
+      (width - 300.0 - 200.0 * index as f64, 30.0)
+   }
+}
+
+fn draw_text(g2d: &mut G2d, glyphs: &mut Glyphs, context: Context, text: &str, position: (f64, f64), size: u32) {
+   let text_color = [0.0, 1.0, 1.0, 1.0];
+   let text_context = context.trans(position.0, position.1);
+   text::Text::new_color(text_color, size).draw(
+      text,
+      glyphs,
+      &text_context.draw_state,
+      text_context.transform,
+      g2d);
+}
+
+fn draw_screen(g2d: &mut G2d, glyphs: &mut Glyphs, context: Context, screen: &Screen, session: &Session, game_state: &GameState, menu: &[MenuItem], pack: &LevelPack, high_scores: &HighScores, unlocks: &Unlocks, player_name: &str, password_entry: &str) {
+   let lines =
+      match *screen {
+         Screen::Title { selection } => {
+            draw_text(g2d, glyphs, context, "BOULDERDASH", (40.0, 80.0), 48);
+            menu.iter()
+               .enumerate()
+               .map(|(i, &item)| {
+                  let marker = if i == selection % menu.len() { ">" } else { " " };
+                  match item {
+                     MenuItem::Pack => format!("{} {}: {}", marker, menu_item_name(item), pack.name.to_uppercase()),
+                     MenuItem::Difficulty => format!("{} {}: {}", marker, menu_item_name(item), session.difficulty + 1),
+                     _ => format!("{} {}", marker, menu_item_name(item))
+                  }
+               })
+               .collect()
+         }
+         Screen::LevelSelect { selection } => {
+            let mut lines = vec!["SELECT CAVE".to_string()];
+            let first = selection.saturating_sub(LEVEL_SELECT_ROWS - 2);
+            for (i, level) in session.levels.iter().enumerate().skip(first).take(LEVEL_SELECT_ROWS) {
+               let record = unlocks.caves[i];
+               let status =
+                  if !record.unlocked {
+                     "LOCKED".to_string()
+                  } else if record.completed {
+                     format!("BEST {:6}  {}", record.best, unlocks::password(&pack.id, i))
+                  } else {
+                     format!("OPEN         {}", unlocks::password(&pack.id, i))
+                  };
+               lines.push(format!("{}{:2}. {:16} {}",
+                  if i == selection { ">" } else { " " },
+                  i + 1, level.name.to_uppercase(), status));
+            }
+            lines
+         }
+         Screen::EnterPassword => {
+            vec![
+               "ENTER PASSWORD".to_string(),
+               format!("CODE: {}_", password_entry)]
+         }
+         Screen::LevelIntro { .. } => {
+            let diamonds = game_state.map.diamond_quota.unwrap_or_else(|| game_state.map.count(Tile::Diamond));
+            let time =
+               match game_state.time_left() {
+                  Some(t) => format!("TIME: {}", t / session.tick_rate),
+                  None => "TIME: UNLIMITED".to_string()
+               };
+            vec![
+               format!("{} {}: {}",
+                  if session.levels[session.level].intermission { "INTERMISSION" } else { "CAVE" },
+                  session.level + 1,
+                  session.levels[session.level].name.to_uppercase()),
+               format!("DIFFICULTY: {}", session.difficulty + 1),
+               format!("DIAMONDS NEEDED: {}", diamonds),
+               time,
+               format!("LIVES: {}", game_state.player_lives),
+               format!("PASSWORD: {}", unlocks::password(&pack.id, session.level))]
+         }
+         Screen::Playing | Screen::Editor => {
+            vec![]
+         }
+         Screen::Paused => {
+            vec!["PAUSED".to_string()]
+         }
+         Screen::LevelComplete { tally, .. } => {
+            vec![
+               format!("CAVE {} COMPLETE", tally.level + 1),
+               format!("DIAMONDS: {}", tally.diamonds),
+               format!("TIME: {}", tally.turn / session.tick_rate),
+               format!("TIME BONUS: {}", tally.bonus),
+               format!("SCORE: {}", tally.score)]
+         }
+         Screen::GameOver { score, level } => {
+            vec![
+               "GAME OVER".to_string(),
+               format!("FINAL SCORE: {}", score),
+               format!("CAVE REACHED: {}", level + 1),
+               "PRESS RETURN".to_string()]
+         }
+         Screen::EnterName { score, .. } => {
+            vec![
+               "NEW HIGH SCORE".to_string(),
+               format!("SCORE: {}", score),
+               format!("NAME: {}_", player_name)]
+         }
+         Screen::HighScores { highlight } => {
+            let mut lines = vec!["HIGH SCORES".to_string()];
+            for (i, e) in high_scores.entries.iter().enumerate() {
+               lines.push(format!("{}{:2}. {:12} {:6}  CAVE {:2}  {}",
+                  if highlight == Some(i) { ">" } else { " " },
+                  i + 1, e.name, e.score, e.level + 1, e.date));
+            }
+            if high_scores.entries.is_empty() {
+               lines.push("NO SCORES YET".to_string());
+            }
+            lines
+         }
+      };
+   let top = if let Screen::Title { .. } = *screen { 160.0 } else { 80.0 };
+   for (i, line) in lines.iter().enumerate() {
+      draw_text(g2d, glyphs, context, line, (40.0, top + 50.0 * i as f64), 32);
+   }
+}
+
+fn draw_rebinding(g2d: &mut G2d, glyphs: &mut Glyphs, context: Context, rebinding: &Rebinding) {
+   draw_text(g2d, glyphs, context, "CONTROLS: press keys or buttons, RETURN to confirm", (40.0, 120.0), 24);
+   for (i, &(action, ref bound)) in rebinding.bindings().iter().enumerate() {
+      let current = rebinding.current() == Some(action);
+      let bound = if current && !rebinding.pending().is_empty() { rebinding.pending() } else { bound.as_slice() };
+      let names: Vec<String> = bound.iter().map(|&c| controls::control_name(c)).collect();
+      let line = format!("{} {}: {}", if current { ">" } else { " " }, controls::action_name(action).to_uppercase(), names.join(", "));
+      draw_text(g2d, glyphs, context, &line, (40.0, 170.0 + 32.0 * i as f64), 24);
+   }
 }
@@ -1,16 +1,19 @@
 use mapping::*;
 use pos::*;
+use rect::Rect;
+use rand::{Rng, SeedableRng, StdRng};
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
 
 
    pub fn put_border_wall(map: &mut LevelMap) {
-      let r = map.rect();
-      for i in r {
-         if i.x == r.min_x
-            || i.x == r.max_x
-            || i.y == r.min_y
-            || i.y == r.max_y {
-            put_wall(map, i);
-         }
+      for i in map.rect().border() {
+         put_wall(map, i);
       }
    }
 
@@ -89,29 +92,292 @@ pub fn level3<'a>() -> Vec<&'a str> {
    data
 }
 
-pub fn read_map(data: &Vec<&str>) -> LevelMap {
-   let x_len = data[0].len();
+pub fn level4<'a>() -> Vec<&'a str> {
+   let mut data = vec![
+      "~~~~~~~~~~~~~~~~~~~~~~~~",
+      "~~*~~~~~~~@~~~~~~~~~~*~~",
+      "~~~~~~~~~~@~~~~~~~~~~~~~",
+      "       ~~~~~~~~~~       ",
+      "~~~~~~~~~~~~~~~~~~~~~~~~",
+      "~~~~~@~~~~~~~~~~~~@~~~~~",
+      "~~~*~@~~~~~~X~~~~~@~*~~~",
+      "~~~~~~~~~~~~~~~~~~~~~~~~",
+      "  %    ~~~~~~~~~~    %  ",
+      "~~~~~~~~~~~~~~~~~~~~~~~~",
+      "~~*~~~~~~~~p~~~~~~~~~*~~",
+      "~~~~~~~~~~~~~~~~~~~~~~~~"];
+   data.reverse();
+   data
+}
+
+pub const DIFFICULTIES: usize = 5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Difficulty {
+   pub time: Option<i64>,
+   pub diamonds: Option<usize>,
+   pub boulders: f64,
+   pub gems: f64
+}
+
+impl Difficulty {
+   pub fn default_for(difficulty: usize) -> Difficulty {
+      Difficulty {
+         time: if difficulty == 0 { None } else { Some(300 - 40 * difficulty as i64) },
+         diamonds: None,
+         boulders: 0.01 * difficulty as f64,
+         gems: 0.005 * difficulty as f64
+      }
+   }
+}
+
+fn default_difficulties() -> Vec<Difficulty> {
+   (0..DIFFICULTIES).map(Difficulty::default_for).collect()
+}
+
+#[derive(Clone)]
+pub struct LevelData {
+   pub name: String,
+   pub rows: Vec<String>,
+   pub wrap: bool,
+   pub intermission: bool,
+   pub difficulties: Vec<Difficulty>
+}
+
+impl LevelData {
+   pub fn new(name: &str, rows: Vec<&str>, wrap: bool) -> LevelData {
+      LevelData {
+         name: name.to_string(),
+         rows: rows.iter().map(|r| r.to_string()).collect(),
+         wrap,
+         intermission: false,
+         difficulties: default_difficulties()
+      }
+   }
+
+   pub fn from_map(name: &str, m: &LevelMap) -> LevelData {
+      let rows = (0..m.y_len())
+         .map(|y| (0..m.x_len()).map(|x| tile_char(m.get(x, y).tile)).collect())
+         .collect();
+      LevelData { name: name.to_string(), rows, wrap: m.wrap, intermission: false, difficulties: default_difficulties() }
+   }
+
+   pub fn difficulty(&self, difficulty: usize) -> Difficulty {
+      self.difficulties.get(difficulty).cloned().unwrap_or_else(|| Difficulty::default_for(difficulty))
+   }
+
+   pub fn to_text(&self) -> String {
+      let mut text = String::new();
+      if !self.name.is_empty() {
+         text.push_str(&format!(";name {}\n", self.name));
+      }
+      if self.wrap {
+         text.push_str(";wrap\n");
+      }
+      for (i, d) in self.difficulties.iter().enumerate() {
+         if *d != Difficulty::default_for(i) {
+            text.push_str(&format!(";difficulty {} time={} diamonds={} boulders={} gems={}\n",
+               i + 1,
+               d.time.unwrap_or(0),
+               d.diamonds.map_or("all".to_string(), |n| n.to_string()),
+               d.boulders,
+               d.gems));
+         }
+      }
+      for row in self.rows.iter().rev() {
+         text.push_str(row);
+         text.push('\n');
+      }
+      text
+   }
+
+   pub fn to_map(&self) -> LevelMap {
+      let mut level_map = read_map(&self.rows);
+      level_map.wrap = self.wrap;
+      level_map
+   }
+
+   pub fn to_map_at(&self, difficulty: usize) -> LevelMap {
+      let settings = self.difficulty(difficulty);
+      let mut level_map = self.to_map();
+      let mut rng: StdRng = SeedableRng::from_seed(&[difficulty + 1][..]);
+      let start = level_map.find_player().map(|p| Rect::around(p, 2));
+      for p in level_map.iter_points() {
+         let roll: f64 = rng.gen();
+         if level_map[p].tile != Tile::Mud || start.map_or(false, |r| r.contains(p)) {
+            continue;
+         }
+         if roll < settings.boulders {
+            level_map[p].tile = Tile::Boulder;
+         } else if roll < settings.boulders + settings.gems {
+            level_map[p].tile = Tile::Diamond;
+         }
+      }
+      if let Some(n) = settings.diamonds {
+         level_map.diamond_quota = Some(::std::cmp::min(n, level_map.count(Tile::Diamond)));
+      }
+      level_map
+   }
+}
+
+pub fn builtin_levels() -> Vec<LevelData> {
+   vec![
+      LevelData::new("First Steps", level1(), false),
+      LevelData::new("Boulder Run", level2(), false),
+      LevelData::new("Deep Mine", level3(), false),
+      LevelData::new("Endless Loop", level4(), true)]
+}
+
+pub fn load_level(levels: &[LevelData], level: usize) -> LevelMap {
+   levels[level].to_map()
+}
+
+pub fn read_level_file<P: AsRef<Path>>(path: P) -> io::Result<LevelData> {
+   let mut text = String::new();
+   File::open(path.as_ref())?.read_to_string(&mut text)?;
+   let mut level = parse_level(&text)?;
+   if level.name.is_empty() {
+      if let Some(stem) = path.as_ref().file_stem() {
+         level.name = stem.to_string_lossy().into_owned();
+      }
+   }
+   Ok(level)
+}
+
+pub fn write_level_file<P: AsRef<Path>>(path: P, level: &LevelData) -> io::Result<()> {
+   File::create(path)?.write_all(level.to_text().as_bytes())
+}
+
+pub fn parse_level(text: &str) -> io::Result<LevelData> {
+   let mut rows = Vec::new();
+   let mut name = String::new();
+   let mut wrap = false;
+   let mut difficulties = default_difficulties();
+   for line in text.lines() {
+      if line.starts_with(';') {
+         let directive = line[1..].trim();
+         if directive == "wrap" {
+            wrap = true;
+         } else if directive.starts_with("name ") {
+            name = directive["name ".len()..].trim().to_string();
+         } else if directive.starts_with("difficulty ") {
+            parse_difficulty(&directive["difficulty ".len()..], &mut difficulties)?;
+         }
+      } else if !line.is_empty() {
+         if let Some(c) = line.chars().find(|&c| tile_from_char(c).is_none()) {
+            return Err(invalid_level(format!("Tile character not recognised. {}", c)));
+         }
+         rows.push(line.to_string());
+      }
+   }
+   if rows.is_empty() {
+      return Err(invalid_level("Level has no rows.".to_string()));
+   }
+   if rows.iter().any(|r| r.chars().count() != rows[0].chars().count()) {
+      return Err(invalid_level("Level rows differ in length.".to_string()));
+   }
+   rows.reverse();
+   let level = LevelData { name, rows, wrap, intermission: false, difficulties };
+   if !is_enclosed(&level.to_map()) {
+      return Err(invalid_level("Level border is not all wall.".to_string()));
+   }
+   Ok(level)
+}
+
+pub fn is_enclosed(map: &LevelMap) -> bool {
+   map.wrap || map.rect().border().all(|p| match map[p].tile {
+      Tile::Wall | Tile::Exit => true,
+      _ => false
+   })
+}
+
+fn parse_difficulty(text: &str, difficulties: &mut Vec<Difficulty>) -> io::Result<()> {
+   let invalid = || invalid_level(format!("Difficulty not recognised. {}", text));
+   let mut parts = text.split_whitespace();
+   let n = parts.next()
+      .and_then(|n| n.parse::<usize>().ok())
+      .filter(|&n| 1 <= n && n <= DIFFICULTIES)
+      .ok_or_else(&invalid)?;
+   let d = &mut difficulties[n - 1];
+   for part in parts {
+      let mut pair = part.splitn(2, '=');
+      match (pair.next(), pair.next()) {
+         (Some("time"), Some(v)) => {
+            d.time = match v.parse().map_err(|_| invalid())? { 0 => None, t => Some(t) };
+         }
+         (Some("diamonds"), Some("all")) => d.diamonds = None,
+         (Some("diamonds"), Some(v)) => d.diamonds = Some(v.parse().map_err(|_| invalid())?),
+         (Some("boulders"), Some(v)) => d.boulders = v.parse().map_err(|_| invalid())?,
+         (Some("gems"), Some(v)) => d.gems = v.parse().map_err(|_| invalid())?,
+         _ => return Err(invalid())
+      }
+   }
+   Ok(())
+}
+
+pub fn read_level_dir<P: AsRef<Path>>(path: P) -> io::Result<Vec<LevelData>> {
+   let mut paths = Vec::new();
+   for entry in fs::read_dir(path)? {
+      let p = entry?.path();
+      if p.extension().map_or(false, |e| e == "txt") {
+         paths.push(p);
+      }
+   }
+   paths.sort();
+   let mut levels = Vec::new();
+   for p in paths {
+      levels.push(read_level_file(&p)?);
+   }
+   if levels.is_empty() {
+      return Err(invalid_level("Level pack has no levels.".to_string()));
+   }
+   Ok(levels)
+}
+
+fn invalid_level(message: String) -> io::Error {
+   io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub fn tile_from_char(c: char) -> Option<Tile> {
+   match c {
+      '#' => { Some(Tile::Wall) }
+      '~' => { Some(Tile::Mud) }
+      '*' => { Some(Tile::Diamond) }
+      '%' => { Some(Tile::Monster) }
+      'p' => { Some(Tile::Player) }
+      'X' => { Some(Tile::Exit) }
+      '.' => { Some(Tile::Empty) }
+      '@' => { Some(Tile::Boulder) }
+      ' ' => { Some(Tile::Empty) }
+      _ => { None }
+   }
+}
+
+pub fn tile_char(t: Tile) -> char {
+   match t {
+      Tile::Wall => '#',
+      Tile::Mud => '~',
+      Tile::Diamond => '*',
+      Tile::Monster => '%',
+      Tile::Player => 'p',
+      Tile::Exit => 'X',
+      Tile::Empty => '.',
+      Tile::Boulder => '@'
+   }
+}
+
+pub fn read_map<S: AsRef<str>>(data: &[S]) -> LevelMap {
+   let x_len = data[0].as_ref().chars().count();
    let y_len = data.len();
-   println!("x len, y len = {}, {}", x_len, y_len);
    let mut new_map = LevelMap::new(x_len, y_len);
    let mut p = Pos::zero();
    for row in data {
-      println!("{}", p);
-      for c in row.chars() {
+      for c in row.as_ref().chars() {
          let tile =
-            match c {
-               '#' => { Tile::Wall }
-               '~' => { Tile::Mud }
-               '*' => { Tile::Diamond }
-               '%' => { Tile::Monster }
-               'p' => { Tile::Player }
-               'X' => { Tile::Exit }
-               '.' => { Tile::Empty }
-               '@' => { Tile::Boulder }
-               ' ' => { Tile::Empty }
-               _ => { panic!("Tile character not recognised. {}", c) }
+            match tile_from_char(c) {
+               Some(t) => { t }
+               None => { panic!("Tile character not recognised. {}", c) }
             };
-         print_glyph(tile);
          new_map[p].tile = tile;
          p.x += 1;
       }
@@ -121,3 +387,317 @@ pub fn read_map(data: &Vec<&str>) -> LevelMap {
    new_map
 }
 
+
+pub struct CaveSettings {
+   pub x_len: usize,
+   pub y_len: usize,
+   pub mud: f64,
+   pub boulders: f64,
+   pub diamonds: f64,
+   pub monsters: f64,
+   pub wall_segments: usize
+}
+
+impl CaveSettings {
+   pub fn new(x_len: usize, y_len: usize) -> CaveSettings {
+      CaveSettings {
+         x_len,
+         y_len,
+         mud: 0.6,
+         boulders: 0.12,
+         diamonds: 0.05,
+         monsters: 0.005,
+         wall_segments: 4
+      }
+   }
+}
+
+pub struct Cave {
+   pub map: LevelMap,
+   pub seed: usize,
+   pub player: Pos,
+   pub exit: Pos,
+   pub diamonds: usize
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CaveStyle {
+   Random,
+   Caverns,
+   Rooms,
+   Prefabs
+}
+
+impl CaveStyle {
+   pub fn from_name(name: &str) -> Option<CaveStyle> {
+      match name {
+         "random" => Some(CaveStyle::Random),
+         "caverns" => Some(CaveStyle::Caverns),
+         "rooms" => Some(CaveStyle::Rooms),
+         "prefabs" => Some(CaveStyle::Prefabs),
+         _ => None
+      }
+   }
+
+   pub fn name(&self) -> &'static str {
+      match *self {
+         CaveStyle::Random => "random",
+         CaveStyle::Caverns => "caverns",
+         CaveStyle::Rooms => "rooms",
+         CaveStyle::Prefabs => "prefabs"
+      }
+   }
+}
+
+pub fn generate_cave(settings: &CaveSettings, seed: usize) -> Cave {
+   generate_styled_cave(CaveStyle::Random, settings, seed)
+}
+
+pub fn generate_named_cave(style_name: &str, settings: &CaveSettings, seed: usize) -> Option<Cave> {
+   CaveStyle::from_name(style_name).map(|style| generate_styled_cave(style, settings, seed))
+}
+
+pub fn generate_styled_cave(style: CaveStyle, settings: &CaveSettings, seed: usize) -> Cave {
+   if settings.x_len < 5 || settings.y_len < 5 {
+      panic!("Cave too small to generate. {} x {}", settings.x_len, settings.y_len);
+   }
+   let mut rng: StdRng = SeedableRng::from_seed(&[seed][..]);
+   let mut map = LevelMap::new(settings.x_len, settings.y_len);
+   put_border_wall(&mut map);
+   let interior = map.rect().shrink(1).unwrap();
+   for p in interior {
+      map[p].tile = random_tile(&mut rng, settings);
+   }
+   match style {
+      CaveStyle::Random => put_wall_segments(&mut map, interior, settings.wall_segments, &mut rng),
+      CaveStyle::Caverns => put_caverns(&mut map, interior, &mut rng),
+      CaveStyle::Rooms => put_rooms(&mut map, interior, &mut rng),
+      CaveStyle::Prefabs => put_prefabs(&mut map, interior, &mut rng)
+   }
+   for p in interior {
+      let roll: f64 = rng.gen();
+      if roll < settings.monsters {
+         put_monster(&mut map, p, interior);
+      }
+   }
+   let player = random_pos(&mut rng, interior);
+   let start_area = Rect::around(player, 2).intersection(interior).unwrap();
+   for p in start_area {
+      match map[p].tile {
+         Tile::Monster | Tile::Boulder | Tile::Wall => { map[p].tile = Tile::Mud; }
+         _ => {}
+      }
+   }
+   map[player].tile = Tile::Player;
+   let mut exit = random_pos(&mut rng, interior);
+   while start_area.contains(exit) && start_area != interior {
+      exit = random_pos(&mut rng, interior);
+   }
+   if exit == player {
+      exit = interior.clamp(player.right());
+   }
+   map[exit].tile = Tile::Exit;
+   make_reachable(&mut map, player, Tile::Exit);
+   make_reachable(&mut map, player, Tile::Diamond);
+   let diamonds = map.count(Tile::Diamond);
+   Cave { map, seed, player, exit, diamonds }
+}
+
+fn random_tile<R: Rng>(rng: &mut R, settings: &CaveSettings) -> Tile {
+   let boulders = settings.boulders;
+   let diamonds = boulders + settings.diamonds;
+   let mud = diamonds + settings.mud;
+   let roll: f64 = rng.gen();
+   if roll < boulders {
+      Tile::Boulder
+   } else if roll < diamonds {
+      Tile::Diamond
+   } else if roll < mud {
+      Tile::Mud
+   } else {
+      Tile::Empty
+   }
+}
+
+fn put_wall_segments<R: Rng>(map: &mut LevelMap, interior: Rect, segments: usize, rng: &mut R) {
+   for _ in 0..segments {
+      let length = rng.gen_range(3, 9);
+      let dir = if rng.gen() { Direction::Right } else { Direction::Up };
+      let mut p = random_pos(rng, interior);
+      for _ in 0..length {
+         if !interior.contains(p) {
+            break;
+         }
+         put_wall(map, p);
+         p = p.to(dir);
+      }
+   }
+}
+
+fn put_caverns<R: Rng>(map: &mut LevelMap, interior: Rect, rng: &mut R) {
+   let mut walls = HashSet::new();
+   for p in interior {
+      if rng.gen::<f64>() < 0.45 {
+         walls.insert(p);
+      }
+   }
+   for _ in 0..4 {
+      let mut next = HashSet::new();
+      for p in interior {
+         let count =
+            Rect::around(p, 1)
+               .into_iter()
+               .filter(|q| !interior.contains(*q) || walls.contains(q))
+               .count();
+         if count >= 5 {
+            next.insert(p);
+         }
+      }
+      walls = next;
+   }
+   for p in walls {
+      put_wall(map, p);
+   }
+}
+
+fn put_rooms<R: Rng>(map: &mut LevelMap, interior: Rect, rng: &mut R) {
+   let band_height = 6;
+   let mut y = interior.min_y + band_height;
+   while y + 2 <= interior.max_y {
+      for x in interior.min_x..interior.max_x + 1 {
+         put_wall(map, Pos { x, y });
+      }
+      for _ in 0..rng.gen_range(1, 3) {
+         let gap = rng.gen_range(interior.min_x, interior.max_x + 1);
+         map[Pos { x: gap, y }].tile = Tile::Mud;
+      }
+      y += band_height + 1;
+   }
+   let mut band_min_y = interior.min_y;
+   while band_min_y <= interior.max_y {
+      let band_max_y = ::std::cmp::min(band_min_y + band_height - 1, interior.max_y);
+      let corridor_y = rng.gen_range(band_min_y, band_max_y + 1);
+      for x in interior.min_x..interior.max_x + 1 {
+         map[Pos { x, y: corridor_y }].tile = Tile::Empty;
+      }
+      let mut x = interior.min_x + rng.gen_range(5, 12);
+      while x + 2 <= interior.max_x {
+         for y in band_min_y..band_max_y + 1 {
+            if y != corridor_y {
+               put_wall(map, Pos { x, y });
+            }
+         }
+         x += rng.gen_range(5, 12);
+      }
+      band_min_y = band_max_y + 2;
+   }
+}
+
+pub fn prefabs<'a>() -> Vec<Vec<&'a str>> {
+   vec![
+      vec![
+         "#####",
+         "#*@*#",
+         "#* *#",
+         "## ##"],
+      vec![
+         "@@@@@",
+         "~***~",
+         "~~~~~"],
+      vec![
+         "#######",
+         "#  %  #",
+         "#     #",
+         "###~###"],
+      vec![
+         "~~#~~",
+         "~*#*~",
+         "~~#~~"]]
+}
+
+fn put_prefabs<R: Rng>(map: &mut LevelMap, interior: Rect, rng: &mut R) {
+   let chunks = prefabs();
+   let count = interior.width() * interior.height() / 80 + 1;
+   for _ in 0..count {
+      let chunk = rng.choose(&chunks).unwrap();
+      stamp(map, random_pos(rng, interior), chunk, interior);
+   }
+}
+
+pub fn stamp(map: &mut LevelMap, at: Pos, chunk: &[&str], bounds: Rect) {
+   for (dy, row) in chunk.iter().rev().enumerate() {
+      for (dx, c) in row.chars().enumerate() {
+         let p = Pos { x: at.x + dx, y: at.y + dy };
+         if bounds.contains(p) {
+            map[p].tile = tile_from_char(c).expect("Tile character not recognised.");
+         }
+      }
+   }
+}
+
+fn random_pos<R: Rng>(rng: &mut R, r: Rect) -> Pos {
+   Pos {
+      x: rng.gen_range(r.min_x, r.max_x + 1),
+      y: rng.gen_range(r.min_y, r.max_y + 1)
+   }
+}
+
+fn put_monster(map: &mut LevelMap, p: Pos, interior: Rect) {
+   for q in Rect::around(p, 1).intersection(interior).unwrap() {
+      if map[q].tile != Tile::Wall {
+         map[q].tile = Tile::Empty;
+      }
+   }
+   map[p].tile = Tile::Monster;
+}
+
+pub fn is_passable(t: Tile) -> bool {
+   match t {
+      Tile::Wall | Tile::Boulder => false,
+      _ => true
+   }
+}
+
+pub fn reachable_from(map: &LevelMap, start: Pos) -> HashSet<Pos> {
+   let mut reached = HashSet::new();
+   let mut queue = VecDeque::new();
+   reached.insert(start);
+   queue.push_back(start);
+   while let Some(p) = queue.pop_front() {
+      for n in map.neighbours(p) {
+         if is_passable(map[n].tile) && reached.insert(n) {
+            queue.push_back(n);
+         }
+      }
+   }
+   reached
+}
+
+fn make_reachable(map: &mut LevelMap, start: Pos, target: Tile) {
+   let mut reached = reachable_from(map, start);
+   let targets: Vec<Pos> =
+      map.iter_points()
+         .filter(|&p| map[p].tile == target)
+         .collect();
+   for t in targets {
+      if !reached.contains(&t) {
+         carve_tunnel(map, start, t);
+         reached = reachable_from(map, start);
+      }
+   }
+}
+
+fn carve_tunnel(map: &mut LevelMap, from: Pos, to: Pos) {
+   let mut p = from;
+   while p != to {
+      p =
+         if p.x < to.x { p.right() }
+         else if p.x > to.x { p.left() }
+         else if p.y < to.y { p.up() }
+         else { p.down() };
+      match map[p].tile {
+         Tile::Wall | Tile::Boulder | Tile::Monster => { map[p].tile = Tile::Mud; }
+         _ => {}
+      }
+   }
+}
@@ -22,11 +22,27 @@ impl Default for Tile {
    }
 }
 
+pub fn tile_from_name(name: &str) -> Option<Tile> {
+   match name {
+      "player" => Some(Tile::Player),
+      "wall" => Some(Tile::Wall),
+      "boulder" => Some(Tile::Boulder),
+      "monster" => Some(Tile::Monster),
+      "diamond" => Some(Tile::Diamond),
+      "exit" => Some(Tile::Exit),
+      "mud" => Some(Tile::Mud),
+      "empty" => Some(Tile::Empty),
+      _ => None
+   }
+}
+
 #[derive(Copy, Clone)]
 pub struct MapCell {
    pub tile: Tile,
    pub cool_down: i64,
-   pub facing: Option<Direction>
+   pub facing: Option<Direction>,
+   pub moved_from: Option<Pos>,
+   pub move_ticks: i64
 }
 
 impl Default for MapCell {
@@ -34,7 +50,9 @@ impl Default for MapCell {
       Self {
          tile: Tile::default(),
          cool_down: 0,
-         facing: None
+         facing: None,
+         moved_from: None,
+         move_ticks: 0
       }
    }
 }
@@ -51,7 +69,9 @@ impl MapCell {
 
 #[derive(Clone)]
 pub struct LevelMap {
-   data: Vec<Vec<MapCell>>
+   data: Vec<Vec<MapCell>>,
+   pub wrap: bool,
+   pub diamond_quota: Option<usize>
 }
 
 
@@ -60,7 +80,7 @@ pub struct LevelMap {
 impl LevelMap {
    pub fn new(x_len: usize, y_len: usize) -> LevelMap {
       let data = make_2d_vec(x_len, y_len, MapCell::default());
-      LevelMap { data: data }
+      LevelMap { data: data, wrap: false, diamond_quota: None }
    }
    pub fn x_max(&self) -> usize { self.x_len() - 1 }
    pub fn y_max(&self) -> usize { self.y_len() - 1 }
@@ -73,12 +93,64 @@ impl LevelMap {
    pub fn get(&self, x: usize, y: usize) -> MapCell {
       self.data[x][y]
    }
+   pub fn move_tile(&mut self, from: Pos, to: Pos, tile: Tile, ticks: i64) {
+      self[from].tile = Tile::Empty;
+      self[to].tile = tile;
+      self[to].cool_down = ticks;
+      self[to].moved_from = Some(from);
+      self[to].move_ticks = ticks;
+   }
+   pub fn move_offset(&self, p: Pos, frame: f64) -> Option<(f64, f64)> {
+      let c = self[p];
+      let from = c.moved_from?;
+      if c.cool_down <= 0 || c.move_ticks <= 0 {
+         return None;
+      }
+      let mut dx = from.x as i64 - p.x as i64;
+      let mut dy = from.y as i64 - p.y as i64;
+      if dx.abs() > 1 {
+         dx -= dx.signum() * self.x_len() as i64;
+      }
+      if dy.abs() > 1 {
+         dy -= dy.signum() * self.y_len() as i64;
+      }
+      let remaining = (c.cool_down.min(c.move_ticks) as f64 - frame.max(0.0).min(1.0)).max(0.0) / c.move_ticks as f64;
+      Some((dx as f64 * remaining, dy as f64 * remaining))
+   }
    pub fn iter_points(&self) -> RectIterator {
       self.rect().into_iter()
    }
    pub fn rect(&self) -> Rect {
       Rect { min_x: 0, min_y: 0, max_x: self.x_len() - 1, max_y: self.y_len() - 1 }
    }
+   pub fn iter_rect<'a>(&'a self, r: Rect) -> SubRectIterator<'a> {
+      SubRectIterator { i: self.rect().intersection(r).map(RectIterator::new), m: self }
+   }
+   pub fn to(&self, p: Pos, dir: Direction) -> Pos {
+      if self.wrap {
+         p.wrapping_to(dir, self.x_len(), self.y_len())
+      } else {
+         p.to(dir)
+      }
+   }
+   pub fn neighbours(&self, p: Pos) -> Vec<Pos> {
+      let mut ns = Vec::new();
+      for &d in &[Direction::Up, Direction::Down, Direction::Left, Direction::Right] {
+         let edge = match d {
+            Direction::Up => p.y == self.y_max(),
+            Direction::Down => p.y == 0,
+            Direction::Left => p.x == 0,
+            Direction::Right => p.x == self.x_max()
+         };
+         if self.wrap || !edge {
+            ns.push(self.to(p, d));
+         }
+      }
+      ns
+   }
+   pub fn count(&self, tile: Tile) -> usize {
+      self.into_iter().filter(|&(_, c)| c.tile == tile).count()
+   }
    pub fn has_no_diamonds(&self) -> bool {
       for (i, c) in self {
          if c.tile == Tile::Diamond {
@@ -87,6 +159,24 @@ impl LevelMap {
       }
       true
    }
+   pub fn exit_open(&self) -> bool {
+      self.diamond_quota == Some(0) || self.has_no_diamonds()
+   }
+}
+
+pub struct SubRectIterator<'a> {
+   i: Option<RectIterator>,
+   m: &'a LevelMap
+}
+
+impl<'a> Iterator for SubRectIterator<'a> {
+   type Item = (Pos, MapCell);
+   fn next(&mut self) -> Option<Self::Item> {
+      match self.i {
+         Some(ref mut i) => i.next().map(|p| (p, self.m[p])),
+         None => None
+      }
+   }
 }
 
 pub struct LevelIntoIterator {
@@ -187,17 +277,20 @@ pub fn print_map(m: &LevelMap) {
 }
 
 pub fn print_glyph(t: Tile) {
-   let c = match t {
-      Tile::Player => "@",
-      Tile::Wall => "#",
-      Tile::Boulder => "O",
-      Tile::Diamond => "*",
-      Tile::Exit => "X",
-      Tile::Monster => "H",
-      Tile::Mud => "m",
-      Tile::Empty => "."
-   };
-   print!("{}", c);
+   print!("{}", glyph(t));
+}
+
+pub fn glyph(t: Tile) -> char {
+   match t {
+      Tile::Player => '@',
+      Tile::Wall => '#',
+      Tile::Boulder => 'O',
+      Tile::Diamond => '*',
+      Tile::Exit => 'X',
+      Tile::Monster => 'H',
+      Tile::Mud => 'm',
+      Tile::Empty => '.'
+   }
 }
 
 impl LevelMap {
@@ -0,0 +1,274 @@
+use assets;
+use mapgen::*;
+use sprites::*;
+use mapping::{tile_from_name, Tile};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+pub const MANIFEST: &'static str = "pack.txt";
+pub const PACKS_FOLDER: &'static str = "packs";
+
+pub struct LevelPack {
+   pub name: String,
+   pub id: String,
+   pub levels: Vec<LevelData>,
+   pub textures: Vec<(Tile, PathBuf)>,
+   pub sprites: Sprites,
+   pub unlocks: HashMap<usize, Vec<usize>>
+}
+
+impl LevelPack {
+   pub fn builtin() -> LevelPack {
+      LevelPack {
+         name: "Classic".to_string(),
+         id: String::new(),
+         levels: builtin_levels(),
+         textures: assets::texture_paths(&[]),
+         sprites: assets::default_sprites(),
+         unlocks: HashMap::new()
+      }
+   }
+
+   pub fn load<P: AsRef<Path>>(path: P) -> io::Result<LevelPack> {
+      let path = path.as_ref();
+      let id: String = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+         .chars()
+         .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+         .collect();
+      let mut pack = LevelPack {
+         name: id.clone(),
+         id: id.clone(),
+         levels: Vec::new(),
+         textures: assets::texture_paths(&[]),
+         sprites: assets::default_sprites(),
+         unlocks: HashMap::new()
+      };
+      if path.is_file() && path.extension().map_or(false, |e| e == "txt") {
+         pack.levels.push(read_level_file(path)?);
+         return Ok(pack);
+      }
+      let source =
+         if path.is_dir() {
+            Source::Dir(path.to_path_buf())
+         } else {
+            Source::Archive(read_tar(path)?)
+         };
+      if !source.contains(MANIFEST) {
+         if let Source::Dir(_) = source {
+            pack.levels = read_level_dir(path)?;
+            return Ok(pack);
+         }
+         return Err(invalid_pack("Level pack has no manifest."));
+      }
+      let manifest = String::from_utf8(source.read(MANIFEST)?)
+         .map_err(|_| invalid_pack("Level pack manifest is not text."))?;
+      let mut overrides = Vec::new();
+      let mut animations = Vec::new();
+      for line in manifest.lines().map(|l| l.trim()) {
+         if line.is_empty() || line.starts_with(';') {
+            continue;
+         }
+         let (keyword, rest) = split_word(line);
+         match keyword {
+            "name" => pack.name = rest.to_string(),
+            "cave" | "intermission" => {
+               let (file, name) = split_word(rest);
+               let text = String::from_utf8(source.read(file)?)
+                  .map_err(|_| invalid_pack(&format!("Level is not text. {}", file)))?;
+               let mut level = parse_level(&text)?;
+               if !name.is_empty() {
+                  level.name = name.to_string();
+               } else if level.name.is_empty() {
+                  level.name = Path::new(file).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+               }
+               level.intermission = keyword == "intermission";
+               pack.levels.push(level);
+            }
+            "unlock" => {
+               let caves = rest.split_whitespace()
+                  .map(|n| n.parse::<usize>().ok().and_then(|n| n.checked_sub(1)))
+                  .collect::<Option<Vec<usize>>>()
+                  .ok_or_else(|| invalid_pack(&format!("Manifest line not recognised. {}", line)))?;
+               match caves.split_first() {
+                  Some((&level, next)) => {
+                     pack.unlocks.entry(level).or_insert_with(Vec::new).extend_from_slice(next);
+                  }
+                  None => return Err(invalid_pack(&format!("Manifest line not recognised. {}", line)))
+               }
+            }
+            "sprite" => {
+               animations.push(parse_sprite(line, |f| source.file_path(f, &id))?);
+            }
+            "texture" => {
+               let (tile, file) = split_word(rest);
+               let tile = tile_from_name(tile).ok_or_else(|| invalid_pack(&format!("Manifest line not recognised. {}", line)))?;
+               overrides.push((tile, source.file_path(file, &id)?));
+            }
+            _ => {
+               return Err(invalid_pack(&format!("Manifest line not recognised. {}", line)));
+            }
+         }
+      }
+      if pack.levels.is_empty() {
+         return Err(invalid_pack("Level pack has no levels."));
+      }
+      let count = pack.levels.len();
+      if pack.unlocks.iter().any(|(&level, next)| level >= count || next.iter().any(|&n| n >= count)) {
+         return Err(invalid_pack("Unlock order names a cave that is not in the level pack."));
+      }
+      pack.textures = assets::texture_paths(&overrides);
+      for &(tile, _) in &overrides {
+         pack.sprites.remove_tile(tile);
+      }
+      for (tile, state, animation) in animations {
+         pack.sprites.add(tile, state, animation);
+      }
+      Ok(pack)
+   }
+
+   pub fn unlocked_by(&self, level: usize) -> Vec<usize> {
+      self.unlocks.get(&level).cloned().unwrap_or_else(|| vec![level + 1])
+   }
+}
+
+pub fn find_packs() -> Vec<PathBuf> {
+   let mut packs = Vec::new();
+   if let Ok(entries) = fs::read_dir(PACKS_FOLDER) {
+      for entry in entries.filter_map(|e| e.ok()) {
+         let p = entry.path();
+         if p.is_dir() || p.extension().map_or(false, |e| e == "tar") {
+            packs.push(p);
+         }
+      }
+   }
+   packs.sort();
+   packs
+}
+
+enum Source {
+   Dir(PathBuf),
+   Archive(HashMap<String, Vec<u8>>)
+}
+
+impl Source {
+   fn contains(&self, name: &str) -> bool {
+      if !is_safe_name(name) {
+         return false;
+      }
+      match *self {
+         Source::Dir(ref dir) => dir.join(name).is_file(),
+         Source::Archive(ref files) => files.contains_key(name)
+      }
+   }
+
+   fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+      check_name(name)?;
+      match *self {
+         Source::Dir(ref dir) => {
+            let mut data = Vec::new();
+            File::open(dir.join(name))?.read_to_end(&mut data)?;
+            Ok(data)
+         }
+         Source::Archive(ref files) => {
+            files.get(name).cloned().ok_or_else(|| invalid_pack(&format!("File not in level pack. {}", name)))
+         }
+      }
+   }
+
+   fn file_path(&self, name: &str, id: &str) -> io::Result<PathBuf> {
+      check_name(name)?;
+      match *self {
+         Source::Dir(ref dir) => Ok(dir.join(name)),
+         Source::Archive(_) => {
+            let path = env::temp_dir().join(format!("boulderdash_{}", id)).join(name);
+            if let Some(parent) = path.parent() {
+               fs::create_dir_all(parent)?;
+            }
+            File::create(&path)?.write_all(&self.read(name)?)?;
+            Ok(path)
+         }
+      }
+   }
+}
+
+fn read_tar(path: &Path) -> io::Result<HashMap<String, Vec<u8>>> {
+   let mut data = Vec::new();
+   File::open(path)?.read_to_end(&mut data)?;
+   let mut files = HashMap::new();
+   let mut offset = 0;
+   while offset + 512 <= data.len() {
+      let header = &data[offset..offset + 512];
+      if header.iter().all(|&b| b == 0) {
+         break;
+      }
+      let name = tar_string(&header[0..100]);
+      let prefix = tar_string(&header[345..500]);
+      let size = tar_octal(&header[124..136]).ok_or_else(|| invalid_pack("Level pack archive header not recognised."))?;
+      let start = offset + 512;
+      if start + size > data.len() {
+         return Err(invalid_pack("Level pack archive is truncated."));
+      }
+      if header[156] == b'0' || header[156] == 0 {
+         let full = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+         let full = full.trim_start_matches("./").to_string();
+         check_name(&full)?;
+         files.insert(full, data[start..start + size].to_vec());
+      }
+      offset = start + (size + 511) / 512 * 512;
+   }
+   if !files.contains_key(MANIFEST) {
+      let suffix = format!("/{}", MANIFEST);
+      let root = files.keys()
+         .filter(|k| k.ends_with(&suffix))
+         .min_by_key(|k| k.len())
+         .map(|k| k[..k.len() - MANIFEST.len()].to_string());
+      if let Some(root) = root {
+         files = files.into_iter()
+            .filter(|&(ref k, _)| k.starts_with(&root))
+            .map(|(k, v)| (k[root.len()..].to_string(), v))
+            .collect();
+      }
+   }
+   Ok(files)
+}
+
+fn is_safe_name(name: &str) -> bool {
+   !name.is_empty() && Path::new(name).components().all(|c| match c {
+      Component::Normal(_) | Component::CurDir => true,
+      _ => false
+   })
+}
+
+fn check_name(name: &str) -> io::Result<()> {
+   if is_safe_name(name) {
+      Ok(())
+   } else {
+      Err(invalid_pack(&format!("File name not allowed in level pack. {}", name)))
+   }
+}
+
+fn tar_string(field: &[u8]) -> String {
+   let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+   String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn tar_octal(field: &[u8]) -> Option<usize> {
+   let text = tar_string(field);
+   usize::from_str_radix(text.trim(), 8).ok()
+}
+
+fn split_word(s: &str) -> (&str, &str) {
+   match s.find(char::is_whitespace) {
+      Some(i) => (&s[..i], s[i..].trim()),
+      None => (s, "")
+   }
+}
+
+fn invalid_pack(message: &str) -> io::Error {
+   io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
@@ -0,0 +1,268 @@
+use controls::*;
+use pos::Direction;
+use std::collections::HashSet;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputEvent {
+   Press(Control),
+   Release(Control),
+   Axis(u8, f64)
+}
+
+pub struct PlayerInput {
+   held: Vec<(Control, Direction)>,
+   buffered: Option<Direction>,
+   axis: (f64, f64),
+   snap: HashSet<Control>
+}
+
+impl PlayerInput {
+   pub fn new() -> PlayerInput {
+      PlayerInput {
+         held: Vec::new(),
+         buffered: None,
+         axis: (0.0, 0.0),
+         snap: HashSet::new()
+      }
+   }
+
+   pub fn handle(&mut self, controls: &Controls, event: InputEvent) -> Option<Action> {
+      match event {
+         InputEvent::Press(c) => {
+            let action = controls.action(c);
+            match action {
+               Some(Action::Move(d)) => {
+                  self.held.retain(|&(held, _)| held != c);
+                  self.held.push((c, d));
+                  self.buffered = Some(d);
+               }
+               Some(Action::Snap) => {
+                  self.snap.insert(c);
+               }
+               _ => {}
+            }
+            action
+         }
+         InputEvent::Release(c) => {
+            match controls.action(c) {
+               Some(Action::Move(_)) => {
+                  self.held.retain(|&(held, _)| held != c);
+               }
+               Some(Action::Snap) => {
+                  self.snap.remove(&c);
+               }
+               _ => {}
+            }
+            None
+         }
+         InputEvent::Axis(axis, position) => {
+            if axis == controls.x_axis {
+               self.axis.0 = position;
+            } else if axis == controls.y_axis {
+               self.axis.1 = position;
+            }
+            None
+         }
+      }
+   }
+
+   pub fn stick_direction(&self, controls: &Controls) -> Option<Direction> {
+      let (x, y) = self.axis;
+      if x.abs() < controls.deadzone && y.abs() < controls.deadzone {
+         None
+      } else if x.abs() >= y.abs() {
+         Some(if x < 0.0 { Direction::Left } else { Direction::Right })
+      } else {
+         Some(if y < 0.0 { Direction::Up } else { Direction::Down })
+      }
+   }
+
+   pub fn command(&self, controls: &Controls) -> Option<Direction> {
+      self.held.last()
+         .map(|&(_, d)| d)
+         .or(self.buffered)
+         .or_else(|| self.stick_direction(controls))
+   }
+
+   pub fn consume(&mut self) {
+      self.buffered = None;
+   }
+
+   pub fn snap(&self) -> bool {
+      !self.snap.is_empty()
+   }
+
+   pub fn clear(&mut self) {
+      *self = PlayerInput::new();
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use piston_window::Key;
+
+   fn press(input: &mut PlayerInput, controls: &Controls, c: Control) -> Option<Action> {
+      input.handle(controls, InputEvent::Press(c))
+   }
+
+   fn release(input: &mut PlayerInput, controls: &Controls, c: Control) {
+      assert_eq!(input.handle(controls, InputEvent::Release(c)), None);
+   }
+
+   #[test]
+   fn key_press_moves_until_released() {
+      let controls = Controls::default();
+      let mut input = PlayerInput::new();
+      assert_eq!(press(&mut input, &controls, Control::Key(Key::Left)), Some(Action::Move(Direction::Left)));
+      assert_eq!(input.command(&controls), Some(Direction::Left));
+      input.consume();
+      assert_eq!(input.command(&controls), Some(Direction::Left));
+      release(&mut input, &controls, Control::Key(Key::Left));
+      assert_eq!(input.command(&controls), None);
+   }
+
+   #[test]
+   fn button_press_moves_and_snaps() {
+      let controls = Controls::default();
+      let mut input = PlayerInput::new();
+      assert_eq!(press(&mut input, &controls, Control::Button(11)), Some(Action::Move(Direction::Up)));
+      assert_eq!(press(&mut input, &controls, Control::Button(0)), Some(Action::Snap));
+      assert_eq!(input.command(&controls), Some(Direction::Up));
+      assert!(input.snap());
+      release(&mut input, &controls, Control::Button(0));
+      assert!(!input.snap());
+      release(&mut input, &controls, Control::Button(11));
+      input.consume();
+      assert_eq!(input.command(&controls), None);
+   }
+
+   #[test]
+   fn unbound_controls_do_nothing() {
+      let controls = Controls::default();
+      let mut input = PlayerInput::new();
+      assert_eq!(press(&mut input, &controls, Control::Key(Key::Z)), None);
+      assert_eq!(press(&mut input, &controls, Control::Button(30)), None);
+      assert_eq!(input.command(&controls), None);
+      assert!(!input.snap());
+   }
+
+   #[test]
+   fn stick_crosses_the_deadzone_both_ways() {
+      let controls = Controls::default();
+      let mut input = PlayerInput::new();
+      let (x, y) = (controls.x_axis, controls.y_axis);
+      let inside = controls.deadzone / 2.0;
+      let outside = controls.deadzone + 0.1;
+      assert_eq!(input.handle(&controls, InputEvent::Axis(x, inside)), None);
+      assert_eq!(input.command(&controls), None);
+      input.handle(&controls, InputEvent::Axis(x, outside));
+      assert_eq!(input.command(&controls), Some(Direction::Right));
+      input.handle(&controls, InputEvent::Axis(x, -inside));
+      assert_eq!(input.command(&controls), None);
+      input.handle(&controls, InputEvent::Axis(x, -outside));
+      assert_eq!(input.command(&controls), Some(Direction::Left));
+      input.handle(&controls, InputEvent::Axis(x, 0.0));
+      input.handle(&controls, InputEvent::Axis(y, -outside));
+      assert_eq!(input.command(&controls), Some(Direction::Up));
+      input.handle(&controls, InputEvent::Axis(y, outside));
+      assert_eq!(input.command(&controls), Some(Direction::Down));
+      input.handle(&controls, InputEvent::Axis(y, inside));
+      assert_eq!(input.command(&controls), None);
+   }
+
+   #[test]
+   fn stick_follows_the_larger_axis() {
+      let controls = Controls::default();
+      let mut input = PlayerInput::new();
+      input.handle(&controls, InputEvent::Axis(controls.x_axis, 0.6));
+      input.handle(&controls, InputEvent::Axis(controls.y_axis, -0.9));
+      assert_eq!(input.command(&controls), Some(Direction::Up));
+      input.handle(&controls, InputEvent::Axis(controls.x_axis, -1.0));
+      assert_eq!(input.command(&controls), Some(Direction::Left));
+   }
+
+   #[test]
+   fn held_keys_override_the_stick() {
+      let controls = Controls::default();
+      let mut input = PlayerInput::new();
+      input.handle(&controls, InputEvent::Axis(controls.x_axis, 1.0));
+      press(&mut input, &controls, Control::Key(Key::Down));
+      assert_eq!(input.command(&controls), Some(Direction::Down));
+      release(&mut input, &controls, Control::Key(Key::Down));
+      input.consume();
+      assert_eq!(input.command(&controls), Some(Direction::Right));
+   }
+
+   #[test]
+   fn most_recent_direction_wins() {
+      let controls = Controls::default();
+      let mut input = PlayerInput::new();
+      press(&mut input, &controls, Control::Key(Key::Up));
+      press(&mut input, &controls, Control::Key(Key::Left));
+      press(&mut input, &controls, Control::Key(Key::Right));
+      assert_eq!(input.command(&controls), Some(Direction::Right));
+      input.consume();
+      release(&mut input, &controls, Control::Key(Key::Left));
+      assert_eq!(input.command(&controls), Some(Direction::Right));
+      release(&mut input, &controls, Control::Key(Key::Right));
+      assert_eq!(input.command(&controls), Some(Direction::Up));
+      press(&mut input, &controls, Control::Key(Key::Left));
+      release(&mut input, &controls, Control::Key(Key::Up));
+      input.consume();
+      assert_eq!(input.command(&controls), Some(Direction::Left));
+   }
+
+   #[test]
+   fn repeated_press_moves_key_to_the_top() {
+      let controls = Controls::default();
+      let mut input = PlayerInput::new();
+      press(&mut input, &controls, Control::Key(Key::Up));
+      press(&mut input, &controls, Control::Key(Key::Down));
+      press(&mut input, &controls, Control::Key(Key::Up));
+      release(&mut input, &controls, Control::Key(Key::Up));
+      input.consume();
+      assert_eq!(input.command(&controls), Some(Direction::Down));
+   }
+
+   #[test]
+   fn short_tap_is_buffered_until_consumed() {
+      let controls = Controls::default();
+      let mut input = PlayerInput::new();
+      press(&mut input, &controls, Control::Key(Key::D));
+      release(&mut input, &controls, Control::Key(Key::D));
+      assert_eq!(input.command(&controls), Some(Direction::Right));
+      assert_eq!(input.command(&controls), Some(Direction::Right));
+      input.consume();
+      assert_eq!(input.command(&controls), None);
+      input.consume();
+      assert_eq!(input.command(&controls), None);
+   }
+
+   #[test]
+   fn later_tap_replaces_the_buffer() {
+      let controls = Controls::default();
+      let mut input = PlayerInput::new();
+      press(&mut input, &controls, Control::Key(Key::W));
+      release(&mut input, &controls, Control::Key(Key::W));
+      press(&mut input, &controls, Control::Key(Key::A));
+      release(&mut input, &controls, Control::Key(Key::A));
+      assert_eq!(input.command(&controls), Some(Direction::Left));
+      input.consume();
+      assert_eq!(input.command(&controls), None);
+   }
+
+   #[test]
+   fn clear_forgets_everything() {
+      let controls = Controls::default();
+      let mut input = PlayerInput::new();
+      press(&mut input, &controls, Control::Key(Key::Up));
+      press(&mut input, &controls, Control::Key(Key::LShift));
+      input.handle(&controls, InputEvent::Axis(controls.x_axis, 1.0));
+      input.clear();
+      assert_eq!(input.command(&controls), None);
+      assert!(!input.snap());
+      release(&mut input, &controls, Control::Key(Key::Up));
+      assert_eq!(input.command(&controls), None);
+   }
+}
@@ -44,6 +44,14 @@ impl Pos {
          }
       }
    }
+   pub fn wrapping_to(&self, dir: Direction, x_len: usize, y_len: usize) -> Pos {
+      match dir {
+         Direction::Up => self.with_y((self.y + 1) % y_len),
+         Direction::Down => self.with_y((self.y + y_len - 1) % y_len),
+         Direction::Left => self.with_x((self.x + x_len - 1) % x_len),
+         Direction::Right => self.with_x((self.x + 1) % x_len)
+      }
+   }
 }
 
 impl fmt::Display for Pos {
@@ -52,7 +60,7 @@ impl fmt::Display for Pos {
    }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Direction {
    Up,
    Down,
@@ -3,7 +3,7 @@ use std::cmp::max;
 use std::cmp::min;
 use std::fmt;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Rect {
    pub min_x: usize,
    pub min_y: usize,
@@ -15,15 +15,95 @@ impl Rect {
    pub fn new(x1: usize, x2: usize, y1: usize, y2: usize) -> Rect {
       let min_x = min(x1, x2);
       let max_x = max(x1, x2);
-      let min_y = min(x1, x2);
-      let max_y = max(x1, x2);
+      let min_y = min(y1, y2);
+      let max_y = max(y1, y2);
       Rect { min_x, max_x, min_y, max_y }
    }
 
+   pub fn around(p: Pos, radius: usize) -> Rect {
+      Rect {
+         min_x: p.x.saturating_sub(radius),
+         min_y: p.y.saturating_sub(radius),
+         max_x: p.x + radius,
+         max_y: p.y + radius
+      }
+   }
+
+   pub fn width(self) -> usize {
+      self.max_x - self.min_x + 1
+   }
+
+   pub fn height(self) -> usize {
+      self.max_y - self.min_y + 1
+   }
+
    pub fn contains(self, p: Pos) -> bool {
       self.min_x <= p.x && p.x <= self.max_x
          && self.min_y <= p.y && p.y <= self.max_y
    }
+
+   pub fn intersects(self, other: Rect) -> bool {
+      self.intersection(other).is_some()
+   }
+
+   pub fn intersection(self, other: Rect) -> Option<Rect> {
+      let min_x = max(self.min_x, other.min_x);
+      let min_y = max(self.min_y, other.min_y);
+      let max_x = min(self.max_x, other.max_x);
+      let max_y = min(self.max_y, other.max_y);
+      if min_x <= max_x && min_y <= max_y {
+         Some(Rect { min_x, min_y, max_x, max_y })
+      } else {
+         None
+      }
+   }
+
+   pub fn union(self, other: Rect) -> Rect {
+      Rect {
+         min_x: min(self.min_x, other.min_x),
+         min_y: min(self.min_y, other.min_y),
+         max_x: max(self.max_x, other.max_x),
+         max_y: max(self.max_y, other.max_y)
+      }
+   }
+
+   pub fn expand(self, n: usize) -> Rect {
+      Rect {
+         min_x: self.min_x.saturating_sub(n),
+         min_y: self.min_y.saturating_sub(n),
+         max_x: self.max_x + n,
+         max_y: self.max_y + n
+      }
+   }
+
+   pub fn shrink(self, n: usize) -> Option<Rect> {
+      if self.width() <= 2 * n || self.height() <= 2 * n {
+         return None;
+      }
+      Some(Rect {
+         min_x: self.min_x + n,
+         min_y: self.min_y + n,
+         max_x: self.max_x - n,
+         max_y: self.max_y - n
+      })
+   }
+
+   pub fn clamp(self, p: Pos) -> Pos {
+      Pos {
+         x: min(max(p.x, self.min_x), self.max_x),
+         y: min(max(p.y, self.min_y), self.max_y)
+      }
+   }
+
+   pub fn is_border(self, p: Pos) -> bool {
+      self.contains(p)
+         && (p.x == self.min_x || p.x == self.max_x
+            || p.y == self.min_y || p.y == self.max_y)
+   }
+
+   pub fn border(self) -> BorderIterator {
+      BorderIterator { i: RectIterator::new(self), r: self }
+   }
 }
 
 impl IntoIterator for Rect {
@@ -70,4 +150,121 @@ impl Iterator for RectIterator {
       }
       None
    }
-}
\ No newline at end of file
+}
+
+pub struct BorderIterator {
+   i: RectIterator,
+   r: Rect
+}
+
+impl Iterator for BorderIterator {
+   type Item = Pos;
+   fn next(&mut self) -> Option<Pos> {
+      while let Some(p) = self.i.next() {
+         if self.r.is_border(p) {
+            return Some(p);
+         }
+      }
+      None
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn pos(x: usize, y: usize) -> Pos {
+      Pos { x, y }
+   }
+
+   #[test]
+   fn new_orders_each_axis_separately() {
+      let r = Rect::new(5, 1, 2, 9);
+      assert_eq!(r, Rect { min_x: 1, max_x: 5, min_y: 2, max_y: 9 });
+      assert_eq!((r.width(), r.height()), (5, 8));
+   }
+
+   #[test]
+   fn intersection_of_overlapping_rects() {
+      let a = Rect::new(0, 4, 0, 4);
+      let b = Rect::new(2, 6, 3, 8);
+      assert_eq!(a.intersection(b), Some(Rect::new(2, 4, 3, 4)));
+      assert_eq!(b.intersection(a), a.intersection(b));
+      assert!(a.intersects(b));
+   }
+
+   #[test]
+   fn intersection_of_disjoint_rects() {
+      let a = Rect::new(0, 2, 0, 2);
+      let b = Rect::new(4, 6, 0, 2);
+      assert_eq!(a.intersection(b), None);
+      assert!(!a.intersects(b));
+      assert_eq!(a.intersection(Rect::new(0, 2, 3, 5)), None);
+   }
+
+   #[test]
+   fn intersection_of_touching_rects() {
+      let a = Rect::new(0, 3, 0, 3);
+      let b = Rect::new(3, 6, 3, 6);
+      assert_eq!(a.intersection(b), Some(Rect::new(3, 3, 3, 3)));
+   }
+
+   #[test]
+   fn union_covers_both_rects() {
+      let a = Rect::new(1, 2, 5, 6);
+      let b = Rect::new(4, 7, 0, 1);
+      assert_eq!(a.union(b), Rect::new(1, 7, 0, 6));
+      assert_eq!(a.union(Rect::new(2, 3, 6, 7)), Rect::new(1, 3, 5, 7));
+      assert_eq!(a.union(a), a);
+   }
+
+   #[test]
+   fn expand_stops_at_zero() {
+      assert_eq!(Rect::new(1, 3, 4, 5).expand(2), Rect::new(0, 5, 2, 7));
+   }
+
+   #[test]
+   fn shrink_down_to_none() {
+      let r = Rect::new(0, 5, 0, 4);
+      assert_eq!(r.shrink(1), Some(Rect::new(1, 4, 1, 3)));
+      assert_eq!(r.shrink(2), Some(Rect::new(2, 3, 2, 2)));
+      assert_eq!(r.shrink(3), None);
+      assert_eq!(Rect::new(0, 0, 0, 0).shrink(0), Some(Rect::new(0, 0, 0, 0)));
+      assert_eq!(Rect::new(0, 1, 0, 9).shrink(1), None);
+   }
+
+   #[test]
+   fn clamp_moves_outside_points_to_the_edge() {
+      let r = Rect::new(2, 5, 3, 7);
+      assert_eq!(r.clamp(pos(4, 4)), pos(4, 4));
+      assert_eq!(r.clamp(pos(0, 9)), pos(2, 7));
+      assert_eq!(r.clamp(pos(8, 1)), pos(5, 3));
+      assert_eq!(r.clamp(pos(3, 0)), pos(3, 3));
+   }
+
+   #[test]
+   fn iteration_is_row_by_row() {
+      let points: Vec<Pos> = Rect::new(1, 2, 4, 5).into_iter().collect();
+      assert_eq!(points, vec![pos(1, 4), pos(2, 4), pos(1, 5), pos(2, 5)]);
+   }
+
+   #[test]
+   fn border_skips_the_interior() {
+      let r = Rect::new(0, 3, 0, 2);
+      let border: Vec<Pos> = r.border().collect();
+      assert_eq!(border, vec![
+         pos(0, 0), pos(1, 0), pos(2, 0), pos(3, 0),
+         pos(0, 1), pos(3, 1),
+         pos(0, 2), pos(1, 2), pos(2, 2), pos(3, 2)]);
+      assert_eq!(border.len(), 2 * (r.width() + r.height()) - 4);
+   }
+
+   #[test]
+   fn border_of_thin_rects_is_every_cell() {
+      let row: Vec<Pos> = Rect::new(2, 6, 1, 1).border().collect();
+      assert_eq!(row, vec![pos(2, 1), pos(3, 1), pos(4, 1), pos(5, 1), pos(6, 1)]);
+      let column: Vec<Pos> = Rect::new(3, 3, 0, 3).border().collect();
+      assert_eq!(column, vec![pos(3, 0), pos(3, 1), pos(3, 2), pos(3, 3)]);
+      assert_eq!(Rect::new(4, 4, 4, 4).border().collect::<Vec<Pos>>(), vec![pos(4, 4)]);
+   }
+}
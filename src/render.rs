@@ -0,0 +1,265 @@
+use assets;
+use game::Session;
+use gif;
+use mapping::*;
+use png;
+use png::HasParameters;
+use pos::*;
+use replay::Replay;
+use util::view_origin;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone)]
+pub struct RgbaImage {
+   pub width: usize,
+   pub height: usize,
+   pub data: Vec<u8>
+}
+
+impl RgbaImage {
+   pub fn new(width: usize, height: usize) -> RgbaImage {
+      RgbaImage { width, height, data: vec![0; width * height * 4] }
+   }
+
+   pub fn load_png<P: AsRef<Path>>(path: P) -> io::Result<RgbaImage> {
+      let decoder = png::Decoder::new(File::open(path)?);
+      let (info, mut reader) = decoder.read_info()?;
+      let mut buf = vec![0; info.buffer_size()];
+      reader.next_frame(&mut buf)?;
+      let samples = info.color_type.samples();
+      let mut image = RgbaImage::new(info.width as usize, info.height as usize);
+      for (i, pixel) in buf.chunks(samples).take(image.width * image.height).enumerate() {
+         let rgba = match info.color_type {
+            png::ColorType::Grayscale => [pixel[0], pixel[0], pixel[0], 255],
+            png::ColorType::GrayscaleAlpha => [pixel[0], pixel[0], pixel[0], pixel[1]],
+            png::ColorType::RGB => [pixel[0], pixel[1], pixel[2], 255],
+            png::ColorType::RGBA => [pixel[0], pixel[1], pixel[2], pixel[3]],
+            png::ColorType::Indexed => {
+               return Err(io::Error::new(io::ErrorKind::InvalidData, "Indexed PNG not expanded."));
+            }
+         };
+         image.data[i * 4..i * 4 + 4].copy_from_slice(&rgba);
+      }
+      Ok(image)
+   }
+
+   pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+      let file = BufWriter::new(File::create(path)?);
+      let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+      encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+      let mut writer = encoder.write_header()?;
+      writer.write_image_data(&self.data)?;
+      Ok(())
+   }
+
+   pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+      let i = (y * self.width + x) * 4;
+      [self.data[i], self.data[i + 1], self.data[i + 2], self.data[i + 3]]
+   }
+
+   pub fn put_pixel(&mut self, x: usize, y: usize, rgba: [u8; 4]) {
+      let i = (y * self.width + x) * 4;
+      self.data[i..i + 4].copy_from_slice(&rgba);
+   }
+
+   pub fn scaled(&self, scale: usize) -> RgbaImage {
+      let mut image = RgbaImage::new(self.width * scale, self.height * scale);
+      for y in 0..image.height {
+         for x in 0..image.width {
+            image.put_pixel(x, y, self.pixel(x / scale, y / scale));
+         }
+      }
+      image
+   }
+
+   pub fn blit(&mut self, source: &RgbaImage, x: i64, y: i64) {
+      for sy in 0..source.height {
+         for sx in 0..source.width {
+            let tx = x + sx as i64;
+            let ty = y + sy as i64;
+            if 0 <= tx && tx < self.width as i64 && 0 <= ty && ty < self.height as i64 {
+               self.put_pixel(tx as usize, ty as usize, source.pixel(sx, sy));
+            }
+         }
+      }
+   }
+}
+
+pub struct TileSet {
+   pub tiles: HashMap<Tile, RgbaImage>,
+   pub tile_size: (usize, usize)
+}
+
+impl TileSet {
+   pub fn load(textures: &[(Tile, PathBuf)]) -> io::Result<TileSet> {
+      let mut tiles = HashMap::new();
+      let mut tile_size = (16, 16);
+      for &(t, ref path) in textures {
+         let image = RgbaImage::load_png(path)?;
+         tile_size = (image.width, image.height);
+         tiles.insert(t, image);
+      }
+      Ok(TileSet { tiles, tile_size })
+   }
+}
+
+pub fn display_tile(m: &LevelMap, c: MapCell) -> Tile {
+   if c.tile == Tile::Exit && !m.exit_open() {
+      Tile::Wall
+   } else {
+      c.tile
+   }
+}
+
+pub fn render_map(m: &LevelMap, tile_set: &TileSet) -> RgbaImage {
+   let (tile_w, tile_h) = tile_set.tile_size;
+   let mut image = RgbaImage::new(m.x_len() * tile_w, m.y_len() * tile_h);
+   for (p, c) in m {
+      let x = p.x * tile_w;
+      let y = (m.y_max() - p.y) * tile_h;
+      image.blit(&tile_set.tiles[&display_tile(m, c)], x as i64, y as i64);
+   }
+   image
+}
+
+pub fn render_view(m: &LevelMap, tile_set: &TileSet, focus: Pos, view_size: (usize, usize)) -> RgbaImage {
+   let (tile_w, tile_h) = (tile_set.tile_size.0 as i64, tile_set.tile_size.1 as i64);
+   let (view_w, view_h) = (view_size.0 as i64, view_size.1 as i64);
+   let map_w = m.x_len() as i64 * tile_w;
+   let map_h = m.y_len() as i64 * tile_h;
+   let origin_x = view_origin(focus.x as i64 * tile_w + tile_w / 2, map_w, view_w, m.wrap);
+   let origin_y = view_origin(focus.y as i64 * tile_h + tile_h / 2, map_h, view_h, m.wrap);
+   let (repeat_x, repeat_y) =
+      if m.wrap {
+         (view_w / map_w + 1, view_h / map_h + 1)
+      } else {
+         (0, 0)
+      };
+   let mut image = RgbaImage::new(view_size.0, view_size.1);
+   for (p, c) in m {
+      let tile = &tile_set.tiles[&display_tile(m, c)];
+      for rx in -repeat_x..repeat_x + 1 {
+         for ry in -repeat_y..repeat_y + 1 {
+            let x = p.x as i64 * tile_w + rx * map_w - origin_x;
+            let y = view_h - ((p.y as i64 + 1) * tile_h + ry * map_h - origin_y);
+            if -tile_w < x && x < view_w && -tile_h < y && y < view_h {
+               image.blit(tile, x, y);
+            }
+         }
+      }
+   }
+   image
+}
+
+pub struct GifSettings {
+   pub textures: Vec<(Tile, PathBuf)>,
+   pub scale: usize,
+   pub frame_skip: usize,
+   pub view_size: Option<(usize, usize)>
+}
+
+impl GifSettings {
+   pub fn new() -> GifSettings {
+      GifSettings {
+         textures: assets::texture_paths(&[]),
+         scale: 1,
+         frame_skip: 6,
+         view_size: None
+      }
+   }
+}
+
+pub fn save_replay_gif<P: AsRef<Path>>(replay: &Replay, session: &mut Session, settings: &GifSettings, path: P) -> io::Result<()> {
+   let tile_set = TileSet::load(&settings.textures)?;
+   let (tile_w, tile_h) = tile_set.tile_size;
+   let level_map = replay.start(session).map;
+   let tick_rate = session.tick_rate;
+   let view_size = settings.view_size.unwrap_or((level_map.x_len() * tile_w, level_map.y_len() * tile_h));
+   let scale = ::std::cmp::max(settings.scale, 1);
+   let frame_skip = ::std::cmp::max(settings.frame_skip, 1);
+   let (width, height) = (view_size.0 * scale, view_size.1 * scale);
+   if width > u16::max_value() as usize || height > u16::max_value() as usize {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "GIF frame too large."));
+   }
+   let palette = Palette::new(&tile_set);
+   let mut encoder = gif::Encoder::new(BufWriter::new(File::create(path)?), width as u16, height as u16, &palette.rgb)?;
+   gif::SetParameter::set(&mut encoder, gif::Repeat::Infinite)?;
+   let delay = ::std::cmp::max(frame_skip as i64 * 100 / tick_rate, 2) as u16;
+   let mut focus = level_map.find_player().unwrap_or(Pos::zero());
+   let mut result = Ok(());
+   replay.play(session, |game_state, tick| {
+      if result.is_err() {
+         return;
+      }
+      if let Some(p) = game_state.map.find_player() {
+         focus = p;
+      }
+      let finished = game_state.level_complete || !game_state.player_alive;
+      if tick % frame_skip == 0 || finished {
+         let view = render_view(&game_state.map, &tile_set, focus, view_size).scaled(scale);
+         let mut frame = gif::Frame::default();
+         frame.width = width as u16;
+         frame.height = height as u16;
+         frame.delay = delay;
+         frame.buffer = palette.indices(&view).into();
+         result = encoder.write_frame(&frame);
+      }
+   });
+   result
+}
+
+struct Palette {
+   colors: Vec<[u8; 4]>,
+   rgb: Vec<u8>
+}
+
+impl Palette {
+   fn new(tile_set: &TileSet) -> Palette {
+      let mut colors = vec![[0, 0, 0, 0]];
+      for image in tile_set.tiles.values() {
+         for pixel in image.data.chunks(4) {
+            let rgba = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            if colors.len() < 256 && !colors.contains(&rgba) {
+               colors.push(rgba);
+            }
+         }
+      }
+      let rgb = colors.iter().flat_map(|c| vec![c[0], c[1], c[2]]).collect();
+      Palette { colors, rgb }
+   }
+
+   fn index_of(&self, rgba: [u8; 4]) -> u8 {
+      match self.colors.iter().position(|c| *c == rgba) {
+         Some(i) => i as u8,
+         None => {
+            let distance = |c: &[u8; 4]| {
+               (0..4).map(|i| (c[i] as i64 - rgba[i] as i64).pow(2)).sum::<i64>()
+            };
+            let mut best = 0;
+            for (i, c) in self.colors.iter().enumerate() {
+               if distance(c) < distance(&self.colors[best]) {
+                  best = i;
+               }
+            }
+            best as u8
+         }
+      }
+   }
+
+   fn indices(&self, image: &RgbaImage) -> Vec<u8> {
+      let mut cache = HashMap::new();
+      image.data.chunks(4).map(|pixel| {
+         let rgba = [pixel[0], pixel[1], pixel[2], pixel[3]];
+         *cache.entry(rgba).or_insert_with(|| self.index_of(rgba))
+      }).collect()
+   }
+}
+
+pub fn save_screenshot<P: AsRef<Path>>(m: &LevelMap, textures: &[(Tile, PathBuf)], path: P) -> io::Result<()> {
+   let tile_set = TileSet::load(textures)?;
+   render_map(m, &tile_set).save_png(path)
+}
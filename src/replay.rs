@@ -0,0 +1,179 @@
+use game::*;
+use mapping::*;
+use pos::*;
+use mapgen::DIFFICULTIES;
+use solver::Move;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+   pub level: usize,
+   pub seed: usize,
+   pub lives: i64,
+   pub difficulty: usize,
+   pub tick_rate: i64,
+   pub commands: Vec<(Option<Direction>, bool)>
+}
+
+impl Replay {
+   pub fn new(level: usize, seed: usize, lives: i64) -> Replay {
+      Replay { level, seed, lives, difficulty: 0, tick_rate: TICKS_PER_SECOND, commands: Vec::new() }
+   }
+
+   pub fn from_moves(level: usize, seed: usize, moves: &[Move]) -> Replay {
+      let mut replay = Replay::new(level, seed, 1);
+      for m in moves {
+         for _ in 0..m.ticks {
+            replay.record(m.command, false);
+         }
+      }
+      replay
+   }
+
+   pub fn record(&mut self, command: Option<Direction>, snap: bool) {
+      self.commands.push((command, snap));
+   }
+
+   pub fn start(&self, session: &mut Session) -> GameState {
+      session.start_level = self.level;
+      session.lives = self.lives;
+      session.difficulty = self.difficulty;
+      session.tick_rate = self.tick_rate;
+      session.start(self.seed)
+   }
+
+   pub fn play<F>(&self, session: &mut Session, mut on_tick: F) -> GameState
+      where F: FnMut(&GameState, usize) {
+      let mut game_state = self.start(session);
+      on_tick(&game_state, 0);
+      for (i, &(command, snap)) in self.commands.iter().enumerate() {
+         game_state.player_command = command;
+         game_state.player_snap = snap;
+         update_game(&mut game_state);
+         on_tick(&game_state, i + 1);
+         if game_state.level_complete || !game_state.player_alive {
+            break;
+         }
+      }
+      game_state
+   }
+
+   pub fn play_session<F>(&self, session: &mut Session, mut on_tick: F) -> GameState
+      where F: FnMut(&GameState, usize) {
+      let mut game_state = self.start(session);
+      on_tick(&game_state, 0);
+      for (i, &(command, snap)) in self.commands.iter().enumerate() {
+         game_state.player_command = command;
+         game_state.player_snap = snap;
+         advance_game(&mut game_state, session);
+         on_tick(&game_state, i + 1);
+      }
+      game_state
+   }
+
+   pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+      let mut file = File::create(path)?;
+      writeln!(file, "level {}", self.level + 1)?;
+      writeln!(file, "seed {}", self.seed)?;
+      writeln!(file, "lives {}", self.lives)?;
+      if self.difficulty > 0 {
+         writeln!(file, "difficulty {}", self.difficulty + 1)?;
+      }
+      if self.tick_rate != TICKS_PER_SECOND {
+         writeln!(file, "ticks {}", self.tick_rate)?;
+      }
+      let mut i = 0;
+      while i < self.commands.len() {
+         let command = self.commands[i];
+         let mut run = 1;
+         while i + run < self.commands.len() && self.commands[i + run] == command {
+            run += 1;
+         }
+         if command.1 {
+            writeln!(file, "{} {} snap", run, command_name(command.0))?;
+         } else {
+            writeln!(file, "{} {}", run, command_name(command.0))?;
+         }
+         i += run;
+      }
+      Ok(())
+   }
+
+   pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Replay> {
+      let file = BufReader::new(File::open(path)?);
+      let mut replay = Replay::new(0, 0, 3);
+      for line in file.lines() {
+         let line = line?;
+         let parts: Vec<&str> = line.split_whitespace().collect();
+         match parts.as_slice() {
+            [] => {}
+            ["level", n] => {
+               let n: usize = n.parse().map_err(|_| invalid_data(&line))?;
+               if n == 0 {
+                  return Err(invalid_data(&line));
+               }
+               replay.level = n - 1;
+            }
+            ["seed", n] => {
+               replay.seed = n.parse().map_err(|_| invalid_data(&line))?;
+            }
+            ["lives", n] => {
+               replay.lives = n.parse().map_err(|_| invalid_data(&line))?;
+            }
+            ["difficulty", n] => {
+               let n: usize = n.parse().map_err(|_| invalid_data(&line))?;
+               if n == 0 || n > DIFFICULTIES {
+                  return Err(invalid_data(&line));
+               }
+               replay.difficulty = n - 1;
+            }
+            ["ticks", n] => {
+               replay.tick_rate = n.parse().map_err(|_| invalid_data(&line))?;
+               if replay.tick_rate <= 0 {
+                  return Err(invalid_data(&line));
+               }
+            }
+            [run, name] | [run, name, "snap"] => {
+               let run: usize = run.parse().map_err(|_| invalid_data(&line))?;
+               let command = command_from_name(name).ok_or_else(|| invalid_data(&line))?;
+               let snap = parts.len() == 3;
+               for _ in 0..run {
+                  replay.record(command, snap);
+               }
+            }
+            _ => {
+               return Err(invalid_data(&line));
+            }
+         }
+      }
+      Ok(replay)
+   }
+}
+
+fn invalid_data(line: &str) -> io::Error {
+   io::Error::new(io::ErrorKind::InvalidData, format!("Replay line not recognised. {}", line))
+}
+
+pub fn command_name(command: Option<Direction>) -> &'static str {
+   match command {
+      Some(Direction::Up) => "up",
+      Some(Direction::Down) => "down",
+      Some(Direction::Left) => "left",
+      Some(Direction::Right) => "right",
+      None => "none"
+   }
+}
+
+pub fn command_from_name(name: &str) -> Option<Option<Direction>> {
+   match name {
+      "up" => Some(Some(Direction::Up)),
+      "down" => Some(Some(Direction::Down)),
+      "left" => Some(Some(Direction::Left)),
+      "right" => Some(Some(Direction::Right)),
+      "none" => Some(None),
+      _ => None
+   }
+}
@@ -0,0 +1,146 @@
+use game::*;
+use mapgen::*;
+use pos::Pos;
+use replay::{command_from_name, command_name};
+use util::GameRng;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+pub fn save_game<P: AsRef<Path>>(path: P, g: &GameState, session: &Session) -> io::Result<()> {
+   let temp_path = path.as_ref().with_extension("tmp");
+   {
+      let mut file = File::create(&temp_path)?;
+      let (seed, draws) = g.rng.state();
+      writeln!(file, "level {}", session.level + 1)?;
+      writeln!(file, "start {}", session.start_level + 1)?;
+      writeln!(file, "level_score {}", session.level_score)?;
+      writeln!(file, "difficulty {}", session.difficulty + 1)?;
+      writeln!(file, "score {}", g.score)?;
+      writeln!(file, "lives {}", g.player_lives)?;
+      writeln!(file, "turn {}", g.turn)?;
+      writeln!(file, "crush {} {}", g.crush, g.crush_resistance)?;
+      writeln!(file, "rng {} {}", seed, draws)?;
+      writeln!(file, "diamonds {}", g.diamonds)?;
+      if g.map.wrap {
+         writeln!(file, "wrap")?;
+      }
+      if let Some(n) = g.map.diamond_quota {
+         writeln!(file, "quota {}", n)?;
+      }
+      let m = &g.map;
+      for y in (0..m.y_len()).rev() {
+         let row: String = (0..m.x_len()).map(|x| tile_char(m.get(x, y).tile)).collect();
+         writeln!(file, "row {}", row)?;
+      }
+      for y in (0..m.y_len()).rev() {
+         let cells: Vec<String> = (0..m.x_len())
+            .map(|x| m.get(x, y))
+            .map(|c| format!("{}/{}", c.cool_down, command_name(c.facing)))
+            .collect();
+         writeln!(file, "cells {}", cells.join(" "))?;
+      }
+   }
+   fs::rename(&temp_path, path)
+}
+
+pub fn load_game<P: AsRef<Path>>(path: P, session: &mut Session) -> io::Result<GameState> {
+   let file = BufReader::new(File::open(path)?);
+   let (mut level, mut start_level, mut level_score, mut difficulty) = (None, 0, 0, 0);
+   let (mut diamonds, mut quota) = (0, None);
+   let (mut score, mut lives, mut turn, mut crush) = (0, 1, 0, (30, 30));
+   let mut rng = None;
+   let mut wrap = false;
+   let mut rows = Vec::new();
+   let mut cells = Vec::new();
+   for line in file.lines() {
+      let line = line?;
+      let parts: Vec<&str> = line.split_whitespace().collect();
+      match parts.as_slice() {
+         [] => {}
+         ["level", n] => level = Some(parse_index(n, &line)?),
+         ["start", n] => start_level = parse_index(n, &line)?,
+         ["level_score", n] => level_score = parse(n, &line)?,
+         ["difficulty", n] => difficulty = parse_index(n, &line)?,
+         ["diamonds", n] => diamonds = parse(n, &line)?,
+         ["quota", n] => quota = Some(parse(n, &line)?),
+         ["score", n] => score = parse(n, &line)?,
+         ["lives", n] => lives = parse(n, &line)?,
+         ["turn", n] => turn = parse(n, &line)?,
+         ["crush", n, resistance] => crush = (parse(n, &line)?, parse(resistance, &line)?),
+         ["rng", seed, draws] => rng = Some(GameRng::restore(parse(seed, &line)?, parse(draws, &line)?)),
+         ["wrap"] => wrap = true,
+         ["row", row] => rows.push(row.to_string()),
+         ["cells", ..] => {
+            let mut row = Vec::new();
+            for cell in &parts[1..] {
+               let mut fields = cell.splitn(2, '/');
+               let cool_down: i64 = parse(fields.next().unwrap_or(""), &line)?;
+               let facing = fields.next().and_then(command_from_name).ok_or_else(|| invalid_data(&line))?;
+               row.push((cool_down, facing));
+            }
+            cells.push(row);
+         }
+         _ => {
+            return Err(invalid_data(&line));
+         }
+      }
+   }
+   let level = level.ok_or_else(|| invalid_save("Saved game has no level."))?;
+   let rng = rng.ok_or_else(|| invalid_save("Saved game has no random state."))?;
+   if level >= session.levels.len() || start_level >= session.levels.len() || difficulty >= DIFFICULTIES {
+      return Err(invalid_save("Saved level not in this level pack."));
+   }
+   let mut text = String::new();
+   for row in &rows {
+      text.push_str(row);
+      text.push('\n');
+   }
+   let level_data = parse_level(&text)?;
+   if cells.len() != rows.len() || cells.iter().any(|r| r.len() != rows[0].len()) {
+      return Err(invalid_save("Saved cells do not match the map."));
+   }
+   let mut map = level_data.to_map();
+   map.wrap = wrap;
+   map.diamond_quota = quota;
+   for (y, row) in cells.iter().rev().enumerate() {
+      for (x, &(cool_down, facing)) in row.iter().enumerate() {
+         let p = Pos { x, y };
+         map[p].cool_down = cool_down;
+         map[p].facing = facing;
+      }
+   }
+   session.level = level;
+   session.start_level = start_level;
+   session.level_score = level_score;
+   session.difficulty = difficulty;
+   let mut g = session.level_state(score, lives, 0);
+   g.map = map;
+   g.diamonds = diamonds;
+   g.turn = turn;
+   g.crush = crush.0;
+   g.crush_resistance = crush.1;
+   g.rng = rng;
+   Ok(g)
+}
+
+fn parse<T: ::std::str::FromStr>(s: &str, line: &str) -> io::Result<T> {
+   s.parse().map_err(|_| invalid_data(line))
+}
+
+fn parse_index(s: &str, line: &str) -> io::Result<usize> {
+   match parse::<usize>(s, line)? {
+      0 => Err(invalid_data(line)),
+      n => Ok(n - 1)
+   }
+}
+
+fn invalid_data(line: &str) -> io::Error {
+   io::Error::new(io::ErrorKind::InvalidData, format!("Saved game line not recognised. {}", line))
+}
+
+fn invalid_save(message: &str) -> io::Error {
+   io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
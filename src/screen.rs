@@ -0,0 +1,176 @@
+use controls::{Action, Control, BACK_KEY, CONFIRM_KEY};
+use game::*;
+use highscores::HighScores;
+use pos::Direction;
+use unlocks::Unlocks;
+
+pub const INTRO_TICKS: i64 = 2 * TICKS_PER_SECOND;
+pub const TALLY_TICKS: i64 = 3 * TICKS_PER_SECOND;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MenuItem {
+   Continue,
+   Start,
+   SelectCave,
+   Password,
+   Difficulty,
+   Pack,
+   HighScores,
+   Editor,
+   Controls,
+   Quit
+}
+
+pub fn menu_items(can_continue: bool) -> Vec<MenuItem> {
+   let mut items = vec![MenuItem::Start, MenuItem::SelectCave, MenuItem::Password, MenuItem::Difficulty, MenuItem::Pack, MenuItem::HighScores, MenuItem::Editor, MenuItem::Controls, MenuItem::Quit];
+   if can_continue {
+      items.insert(0, MenuItem::Continue);
+   }
+   items
+}
+
+pub fn menu_item_name(item: MenuItem) -> &'static str {
+   match item {
+      MenuItem::Continue => "CONTINUE",
+      MenuItem::Start => "START",
+      MenuItem::SelectCave => "SELECT CAVE",
+      MenuItem::Password => "PASSWORD",
+      MenuItem::Difficulty => "DIFFICULTY",
+      MenuItem::Pack => "PACK",
+      MenuItem::HighScores => "HIGH SCORES",
+      MenuItem::Editor => "EDITOR",
+      MenuItem::Controls => "CONTROLS",
+      MenuItem::Quit => "QUIT"
+   }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Screen {
+   Title { selection: usize },
+   LevelSelect { selection: usize },
+   EnterPassword,
+   LevelIntro { ticks: i64 },
+   Playing,
+   Paused,
+   LevelComplete { tally: Tally, ticks: i64 },
+   GameOver { score: i64, level: usize },
+   EnterName { score: i64, level: usize },
+   HighScores { highlight: Option<usize> },
+   Editor
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScreenEvent {
+   Selected(MenuItem),
+   CaveChosen(usize),
+   PasswordEntered,
+   NameEntered { score: i64, level: usize }
+}
+
+impl Screen {
+   pub fn shows_map(&self) -> bool {
+      match *self {
+         Screen::Playing | Screen::Paused => true,
+         _ => false
+      }
+   }
+
+   pub fn update(&mut self, g: &mut GameState, session: &mut Session) {
+      *self =
+         match *self {
+            Screen::Playing => {
+               match advance_game(g, session) {
+                  Progress::Playing => Screen::Playing,
+                  Progress::LevelComplete(tally) => Screen::LevelComplete { tally, ticks: 0 },
+                  Progress::LifeLost | Progress::LevelSkipped => Screen::LevelIntro { ticks: 0 },
+                  Progress::GameOver { score, level } => Screen::GameOver { score, level }
+               }
+            }
+            Screen::LevelIntro { ticks } if ticks + 1 >= INTRO_TICKS => Screen::Playing,
+            Screen::LevelIntro { ticks } => Screen::LevelIntro { ticks: ticks + 1 },
+            Screen::LevelComplete { ticks, .. } if ticks + 1 >= TALLY_TICKS => Screen::LevelIntro { ticks: 0 },
+            Screen::LevelComplete { tally, ticks } => Screen::LevelComplete { tally, ticks: ticks + 1 },
+            screen => screen
+         };
+   }
+
+   pub fn press(&mut self, action: Option<Action>, control: Control, menu: &[MenuItem], high_scores: &HighScores, unlocks: &Unlocks) -> Option<ScreenEvent> {
+      let confirm = control == Control::Key(CONFIRM_KEY) || action == Some(Action::Snap);
+      match *self {
+         Screen::Title { selection } => {
+            let selection = selection % menu.len();
+            match action {
+               Some(Action::Move(Direction::Up)) => {
+                  *self = Screen::Title { selection: (selection + menu.len() - 1) % menu.len() };
+               }
+               Some(Action::Move(Direction::Down)) => {
+                  *self = Screen::Title { selection: (selection + 1) % menu.len() };
+               }
+               _ if confirm => {
+                  let item = menu[selection];
+                  match item {
+                     MenuItem::Start => *self = Screen::LevelIntro { ticks: 0 },
+                     MenuItem::SelectCave => *self = Screen::LevelSelect { selection: 0 },
+                     MenuItem::Password => *self = Screen::EnterPassword,
+                     MenuItem::HighScores => *self = Screen::HighScores { highlight: None },
+                     MenuItem::Editor => *self = Screen::Editor,
+                     _ => {}
+                  }
+                  return Some(ScreenEvent::Selected(item));
+               }
+               _ => {}
+            }
+         }
+         Screen::LevelSelect { selection } => {
+            let count = unlocks.caves.len();
+            match action {
+               Some(Action::Move(Direction::Up)) => {
+                  *self = Screen::LevelSelect { selection: (selection + count - 1) % count };
+               }
+               Some(Action::Move(Direction::Down)) => {
+                  *self = Screen::LevelSelect { selection: (selection + 1) % count };
+               }
+               _ if confirm && unlocks.is_unlocked(selection) => {
+                  *self = Screen::LevelIntro { ticks: 0 };
+                  return Some(ScreenEvent::CaveChosen(selection));
+               }
+               _ if control == Control::Key(BACK_KEY) => {
+                  *self = Screen::Title { selection: 0 };
+               }
+               _ => {}
+            }
+         }
+         Screen::EnterPassword if control == Control::Key(CONFIRM_KEY) => {
+            return Some(ScreenEvent::PasswordEntered);
+         }
+         Screen::LevelIntro { .. } if confirm => {
+            *self = Screen::Playing;
+         }
+         Screen::LevelComplete { .. } if confirm => {
+            *self = Screen::LevelIntro { ticks: 0 };
+         }
+         Screen::Playing if action == Some(Action::Pause) => {
+            *self = Screen::Paused;
+         }
+         Screen::Paused if action == Some(Action::Pause) || confirm => {
+            *self = Screen::Playing;
+         }
+         Screen::GameOver { score, level } if confirm => {
+            *self =
+               if high_scores.qualifies(score) {
+                  Screen::EnterName { score, level }
+               } else {
+                  Screen::HighScores { highlight: None }
+               };
+         }
+         Screen::EnterName { score, level } if control == Control::Key(CONFIRM_KEY) => {
+            return Some(ScreenEvent::NameEntered { score, level });
+         }
+         Screen::HighScores { .. } if confirm => {
+            *self = Screen::Title { selection: 0 };
+         }
+         _ => {}
+      }
+      None
+   }
+}
@@ -0,0 +1,226 @@
+use game::*;
+use mapgen::*;
+use mapping::*;
+use pos::*;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+
+const MOVE_TICKS: usize = 12;
+const WAIT_TICKS: usize = 10;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Move {
+   pub command: Option<Direction>,
+   pub ticks: usize
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SearchLimit;
+
+pub struct Rules {
+   pub seed: usize,
+   pub max_turns: i64,
+   pub max_states: usize
+}
+
+impl Rules {
+   pub fn new(seed: usize) -> Rules {
+      Rules {
+         seed,
+         max_turns: 5000,
+         max_states: 5000
+      }
+   }
+}
+
+struct Node {
+   cost: usize,
+   index: usize,
+   state: GameState
+}
+
+impl PartialEq for Node {
+   fn eq(&self, other: &Node) -> bool {
+      self.cost == other.cost && self.index == other.index
+   }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+   fn partial_cmp(&self, other: &Node) -> Option<Ordering> {
+      Some(self.cmp(other))
+   }
+}
+
+impl Ord for Node {
+   fn cmp(&self, other: &Node) -> Ordering {
+      other.cost.cmp(&self.cost).then_with(|| other.index.cmp(&self.index))
+   }
+}
+
+pub fn solve(map: &LevelMap, rules: &Rules) -> Result<Option<Vec<Move>>, SearchLimit> {
+   let start = GameState::with_seed(map.clone(), 0, 1, rules.seed);
+   let mut visited = HashSet::new();
+   let mut history: Vec<Option<(usize, Move)>> = vec![None];
+   let mut open = BinaryHeap::new();
+   visited.insert(state_key(&start));
+   open.push(Node { cost: heuristic(&start), index: 0, state: start });
+   let mut expanded = 0;
+   let mut out_of_turns = false;
+   while let Some(node) = open.pop() {
+      expanded += 1;
+      if expanded > rules.max_states {
+         return Err(SearchLimit);
+      }
+      let moves = path_to(&history, node.index).len();
+      let commands = [
+         Some(Direction::Up),
+         Some(Direction::Down),
+         Some(Direction::Left),
+         Some(Direction::Right),
+         None];
+      for &command in &commands {
+         if let Some((next, m)) = step(&node.state, command) {
+            history.push(Some((node.index, m)));
+            let index = history.len() - 1;
+            if next.level_complete {
+               return Ok(Some(path_to(&history, index)));
+            }
+            if next.turn > rules.max_turns {
+               out_of_turns = true;
+               continue;
+            }
+            if !visited.insert(state_key(&next)) {
+               continue;
+            }
+            let cost = moves + 1 + 5 * heuristic(&next);
+            open.push(Node { cost, index, state: next });
+         }
+      }
+   }
+   if out_of_turns {
+      Err(SearchLimit)
+   } else {
+      Ok(None)
+   }
+}
+
+pub fn is_solvable(map: &LevelMap, rules: &Rules) -> Result<bool, SearchLimit> {
+   solve(map, rules).map(|moves| moves.is_some())
+}
+
+pub fn replay(map: &LevelMap, rules: &Rules, moves: &[Move]) -> GameState {
+   let mut state = GameState::with_seed(map.clone(), 0, 1, rules.seed);
+   for m in moves {
+      state.player_command = m.command;
+      for _ in 0..m.ticks {
+         update_game(&mut state);
+      }
+   }
+   state
+}
+
+pub fn generate_solvable_cave(style: CaveStyle, settings: &CaveSettings, seed: usize, rules: &Rules, attempts: usize) -> Option<Cave> {
+   for attempt in 0..attempts {
+      let cave = generate_styled_cave(style, settings, seed + attempt);
+      if is_solvable(&cave.map, rules) == Ok(true) {
+         return Some(cave);
+      }
+   }
+   None
+}
+
+fn step(state: &GameState, command: Option<Direction>) -> Option<(GameState, Move)> {
+   let mut next = state.clone();
+   let start = next.map.find_player();
+   next.player_command = command;
+   let max_ticks = if command.is_some() { MOVE_TICKS } else { WAIT_TICKS };
+   let mut ticks = 0;
+   while ticks < max_ticks {
+      update_game(&mut next);
+      ticks += 1;
+      if !next.player_alive {
+         return None;
+      }
+      if next.level_complete {
+         break;
+      }
+      if command.is_some() && next.map.find_player() != start {
+         break;
+      }
+   }
+   if command.is_some() && !next.level_complete && next.map.find_player() == start {
+      return None;
+   }
+   Some((next, Move { command, ticks }))
+}
+
+fn path_to(history: &Vec<Option<(usize, Move)>>, index: usize) -> Vec<Move> {
+   let mut moves = Vec::new();
+   let mut i = index;
+   while let Some((parent, m)) = history[i] {
+      moves.push(m);
+      i = parent;
+   }
+   moves.reverse();
+   moves
+}
+
+fn state_key(state: &GameState) -> (Vec<(Tile, i64, Option<Direction>)>, u64, i64) {
+   let cells = (&state.map).into_iter()
+      .map(|(_, c)| (c.tile, c.cool_down.max(0), if c.tile == Tile::Monster { c.facing } else { None }))
+      .collect();
+   (cells, state.rng.state().1, state.crush)
+}
+
+fn heuristic(state: &GameState) -> usize {
+   let player = match state.map.find_player() {
+      Some(p) => p,
+      None => return 0
+   };
+   let target = if state.map.exit_open() { Tile::Exit } else { Tile::Diamond };
+   let distance = distance_to(&state.map, player, target).unwrap_or(state.map.x_len() * state.map.y_len());
+   state.map.count(Tile::Diamond) * 10 + distance
+}
+
+fn distance_to(map: &LevelMap, start: Pos, target: Tile) -> Option<usize> {
+   let mut reached = HashSet::new();
+   let mut queue = VecDeque::new();
+   reached.insert(start);
+   queue.push_back((start, 0));
+   while let Some((p, d)) = queue.pop_front() {
+      if map[p].tile == target {
+         return Some(d);
+      }
+      for n in map.neighbours(p) {
+         if is_passable(map[n].tile) && reached.insert(n) {
+            queue.push_back((n, d + 1));
+         }
+      }
+   }
+   None
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn solves_level1_and_the_moves_replay() {
+      let map = LevelData::new("First Steps", level1(), false).to_map();
+      let rules = Rules::new(7);
+      let moves = solve(&map, &rules).expect("search limit reached").expect("no solution found");
+      let mut state = GameState::with_seed(map.clone(), 0, 1, rules.seed);
+      for m in &moves {
+         state.player_command = m.command;
+         for _ in 0..m.ticks {
+            update_game(&mut state);
+         }
+      }
+      assert!(state.level_complete);
+      assert!(state.player_alive);
+   }
+}
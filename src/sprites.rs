@@ -0,0 +1,145 @@
+use mapping::*;
+use pos::*;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST: &'static str = "sprites.txt";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AnimState {
+   Idle,
+   Move(Direction)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animation {
+   pub sheet: PathBuf,
+   pub frame_size: (u32, u32),
+   pub first: usize,
+   pub count: usize,
+   pub ticks: i64
+}
+
+impl Animation {
+   pub fn frame(&self, time: i64, p: Pos) -> usize {
+      let phase = (p.x * 7 + p.y * 13) as i64;
+      self.first + ((time + phase) / self.ticks) as usize % self.count
+   }
+
+   pub fn frame_rect(&self, sheet_size: (u32, u32), frame: usize) -> [f64; 4] {
+      let (w, h) = self.frame_size;
+      let columns = ::std::cmp::max(sheet_size.0 / w, 1) as usize;
+      let (column, row) = (frame % columns, frame / columns);
+      let y = sheet_size.1 as i64 - (row as i64 + 1) * h as i64;
+      [(column as u32 * w) as f64, y as f64, w as f64, h as f64]
+   }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Sprites {
+   pub animations: Vec<(Tile, AnimState, Animation)>
+}
+
+impl Sprites {
+   pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Sprites> {
+      let path = path.as_ref();
+      let mut sprites = Sprites::default();
+      let file = match File::open(path) {
+         Ok(file) => file,
+         Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(sprites),
+         Err(err) => return Err(err)
+      };
+      let dir = path.parent().unwrap_or(Path::new(""));
+      for line in BufReader::new(file).lines() {
+         let line = line?;
+         let line = line.trim();
+         if line.is_empty() || line.starts_with(';') {
+            continue;
+         }
+         let (tile, state, animation) = parse_sprite(line, |f| Ok(dir.join(f)))?;
+         sprites.add(tile, state, animation);
+      }
+      Ok(sprites)
+   }
+
+   pub fn add(&mut self, tile: Tile, state: AnimState, animation: Animation) {
+      self.animations.retain(|&(t, s, _)| t != tile || s != state);
+      self.animations.push((tile, state, animation));
+   }
+
+   pub fn remove_tile(&mut self, tile: Tile) {
+      self.animations.retain(|&(t, _, _)| t != tile);
+   }
+
+   pub fn sheets(&self) -> Vec<PathBuf> {
+      let mut sheets: Vec<PathBuf> = self.animations.iter().map(|&(_, _, ref a)| a.sheet.clone()).collect();
+      sheets.sort();
+      sheets.dedup();
+      sheets
+   }
+
+   pub fn find(&self, tile: Tile, state: AnimState) -> Option<&Animation> {
+      let find = |state| self.animations.iter().find(|&&(t, s, _)| t == tile && s == state).map(|&(_, _, ref a)| a);
+      find(state).or_else(|| find(AnimState::Idle))
+   }
+}
+
+pub fn anim_state(m: &LevelMap, p: Pos) -> AnimState {
+   if let Some((dx, dy)) = m.move_offset(p, 0.0) {
+      let d =
+         if dx.abs() > dy.abs() {
+            if dx < 0.0 { Direction::Right } else { Direction::Left }
+         } else if dy < 0.0 {
+            Direction::Up
+         } else {
+            Direction::Down
+         };
+      return AnimState::Move(d);
+   }
+   match m[p].tile {
+      Tile::Monster => m[p].facing.map_or(AnimState::Idle, AnimState::Move),
+      _ => AnimState::Idle
+   }
+}
+
+pub fn parse_sprite<F>(line: &str, resolve: F) -> io::Result<(Tile, AnimState, Animation)>
+   where F: Fn(&str) -> io::Result<PathBuf> {
+   let parts: Vec<&str> = line.split_whitespace().collect();
+   match parts.as_slice() {
+      ["sprite", tile, state, sheet, size, first, count, ticks] => {
+         let tile = tile_from_name(tile).ok_or_else(|| invalid_data(line))?;
+         let state = state_from_name(state).ok_or_else(|| invalid_data(line))?;
+         let mut size = size.split('x').map(|n| n.parse::<u32>());
+         let frame_size =
+            match (size.next(), size.next(), size.next()) {
+               (Some(Ok(w)), Some(Ok(h)), None) if w > 0 && h > 0 => (w, h),
+               _ => return Err(invalid_data(line))
+            };
+         let first = first.parse().map_err(|_| invalid_data(line))?;
+         let count = count.parse().map_err(|_| invalid_data(line))?;
+         let ticks = ticks.parse().map_err(|_| invalid_data(line))?;
+         if count == 0 || ticks <= 0 {
+            return Err(invalid_data(line));
+         }
+         Ok((tile, state, Animation { sheet: resolve(sheet)?, frame_size, first, count, ticks }))
+      }
+      _ => Err(invalid_data(line))
+   }
+}
+
+fn state_from_name(name: &str) -> Option<AnimState> {
+   match name {
+      "idle" => Some(AnimState::Idle),
+      "up" => Some(AnimState::Move(Direction::Up)),
+      "down" => Some(AnimState::Move(Direction::Down)),
+      "left" => Some(AnimState::Move(Direction::Left)),
+      "right" => Some(AnimState::Move(Direction::Right)),
+      _ => None
+   }
+}
+
+fn invalid_data(line: &str) -> io::Error {
+   io::Error::new(io::ErrorKind::InvalidData, format!("Sprite line not recognised. {}", line))
+}
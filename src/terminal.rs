@@ -0,0 +1,209 @@
+use game::*;
+use mapping::*;
+use pos::*;
+use util::view_origin;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const TICKS_PER_FRAME: u64 = 4;
+const HOLD_TICKS: i64 = 12;
+
+struct RawMode {
+   saved: String
+}
+
+impl RawMode {
+   fn enter() -> RawMode {
+      let saved = save_terminal_mode();
+      set_terminal_mode("raw -echo");
+      print!("\x1b[?25l\x1b[2J");
+      RawMode { saved }
+   }
+}
+
+impl Drop for RawMode {
+   fn drop(&mut self) {
+      print!("\x1b[0m\x1b[?25h\x1b[2J\x1b[H");
+      let _ = io::stdout().flush();
+      set_terminal_mode(&self.saved);
+   }
+}
+
+enum KeyPress {
+   Move(Direction),
+   Quit
+}
+
+pub fn run(session: &mut Session, seed: usize, tick_rate: u64) {
+   let _raw_mode = RawMode::enter();
+   let keys = spawn_key_reader();
+   let mut game_state = session.start(seed);
+   let mut old_player_pos = Pos { x: 1, y: 1 };
+   let mut held = 0;
+   let mut ticks: u64 = 0;
+   let mut screen_size = terminal_size();
+   let tick = Duration::from_millis(1000 / tick_rate);
+   'game: loop {
+      let start = Instant::now();
+      while let Ok(key) = keys.try_recv() {
+         match key {
+            KeyPress::Quit => break 'game,
+            KeyPress::Move(d) => {
+               game_state.player_command = Some(d);
+               held = HOLD_TICKS;
+            }
+         }
+      }
+      if held > 0 {
+         held -= 1;
+         if held == 0 {
+            game_state.player_command = None;
+         }
+      }
+      advance_game(&mut game_state, session);
+      ticks += 1;
+      if ticks % TICKS_PER_FRAME == 0 {
+         if ticks % tick_rate == 0 {
+            screen_size = terminal_size();
+         }
+         if let Some(p) = game_state.map.find_player() {
+            old_player_pos = p;
+         }
+         draw(&game_state, session.level, old_player_pos, screen_size, tick_rate);
+      }
+      let elapsed = start.elapsed();
+      if elapsed < tick {
+         thread::sleep(tick - elapsed);
+      }
+   }
+}
+
+fn draw(game_state: &GameState, level: usize, focus: Pos, screen_size: (usize, usize), tick_rate: u64) {
+   let m = &game_state.map;
+   let (rows, cols) = screen_size;
+   let view_x_len = cols as i64;
+   let view_y_len = rows as i64 - 1;
+   let x0 = view_origin(focus.x as i64, m.x_len() as i64, view_x_len, m.wrap);
+   let y0 = view_origin(focus.y as i64, m.y_len() as i64, view_y_len, m.wrap);
+   let mut out = String::new();
+   out.push_str("\x1b[H\x1b[0m\x1b[2K");
+   out.push_str(&format!(
+      "LEVEL: {}  SCORE: {}  LIVES: {}  TIME: {}  DIAMONDS: {}",
+      level + 1,
+      game_state.score,
+      game_state.player_lives,
+      game_state.turn / tick_rate as i64,
+      m.count(Tile::Diamond)));
+   for row in 0..view_y_len {
+      out.push_str("\r\n");
+      let y = y0 + view_y_len - 1 - row;
+      for col in 0..view_x_len {
+         let x = x0 + col;
+         match map_pos(m, x, y) {
+            Some(p) => {
+               let mut tile = m[p].tile;
+               if tile == Tile::Exit && !m.exit_open() {
+                  tile = Tile::Wall;
+               }
+               out.push_str(&format!("\x1b[{}m{}", tile_color(tile), glyph(tile)));
+            }
+            None => {
+               out.push_str("\x1b[0m ");
+            }
+         }
+      }
+   }
+   let stdout = io::stdout();
+   let mut handle = stdout.lock();
+   handle.write_all(out.as_bytes()).unwrap();
+   handle.flush().unwrap();
+}
+
+fn map_pos(m: &LevelMap, x: i64, y: i64) -> Option<Pos> {
+   let x_len = m.x_len() as i64;
+   let y_len = m.y_len() as i64;
+   if m.wrap {
+      Some(Pos { x: (((x % x_len) + x_len) % x_len) as usize, y: (((y % y_len) + y_len) % y_len) as usize })
+   } else if 0 <= x && x < x_len && 0 <= y && y < y_len {
+      Some(Pos { x: x as usize, y: y as usize })
+   } else {
+      None
+   }
+}
+
+fn tile_color(t: Tile) -> u8 {
+   match t {
+      Tile::Player => 93,
+      Tile::Wall => 37,
+      Tile::Boulder => 90,
+      Tile::Diamond => 96,
+      Tile::Exit => 92,
+      Tile::Monster => 95,
+      Tile::Mud => 33,
+      Tile::Empty => 30
+   }
+}
+
+fn spawn_key_reader() -> Receiver<KeyPress> {
+   let (sender, receiver) = channel();
+   thread::spawn(move || {
+      let stdin = io::stdin();
+      let mut bytes = stdin.lock().bytes();
+      while let Some(Ok(b)) = bytes.next() {
+         let key =
+            match b {
+               b'w' | b'W' => Some(KeyPress::Move(Direction::Up)),
+               b's' | b'S' => Some(KeyPress::Move(Direction::Down)),
+               b'a' | b'A' => Some(KeyPress::Move(Direction::Left)),
+               b'd' | b'D' => Some(KeyPress::Move(Direction::Right)),
+               b'q' | b'Q' | 3 => Some(KeyPress::Quit),
+               27 => {
+                  match (bytes.next(), bytes.next()) {
+                     (Some(Ok(b'[')), Some(Ok(b'A'))) => Some(KeyPress::Move(Direction::Up)),
+                     (Some(Ok(b'[')), Some(Ok(b'B'))) => Some(KeyPress::Move(Direction::Down)),
+                     (Some(Ok(b'[')), Some(Ok(b'D'))) => Some(KeyPress::Move(Direction::Left)),
+                     (Some(Ok(b'[')), Some(Ok(b'C'))) => Some(KeyPress::Move(Direction::Right)),
+                     _ => None
+                  }
+               }
+               _ => None
+            };
+         if let Some(k) = key {
+            if sender.send(k).is_err() {
+               break;
+            }
+         }
+      }
+   });
+   receiver
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+   Command::new("stty")
+      .args(args)
+      .stdin(Stdio::inherit())
+      .output()
+      .ok()
+      .and_then(|o| String::from_utf8(o.stdout).ok())
+}
+
+fn save_terminal_mode() -> String {
+   stty(&["-g"]).map(|s| s.trim().to_string()).unwrap_or_else(|| "sane".to_string())
+}
+
+fn set_terminal_mode(mode: &str) {
+   let args: Vec<&str> = mode.split_whitespace().collect();
+   stty(&args);
+}
+
+fn terminal_size() -> (usize, usize) {
+   let size = stty(&["size"]).unwrap_or_default();
+   let mut parts = size.split_whitespace().map(|s| s.parse::<usize>());
+   match (parts.next(), parts.next()) {
+      (Some(Ok(rows)), Some(Ok(cols))) if rows > 1 && cols > 0 => (rows, cols),
+      _ => (24, 80)
+   }
+}
@@ -0,0 +1,116 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+pub const PASSWORD_LEN: usize = 6;
+const PASSWORD_LETTERS: &'static [u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub struct CaveRecord {
+   pub unlocked: bool,
+   pub completed: bool,
+   pub best: i64
+}
+
+pub struct Unlocks {
+   pub caves: Vec<CaveRecord>
+}
+
+impl Unlocks {
+   pub fn new(count: usize) -> Unlocks {
+      let mut caves = vec![CaveRecord::default(); count];
+      if let Some(first) = caves.first_mut() {
+         first.unlocked = true;
+      }
+      Unlocks { caves }
+   }
+
+   pub fn load<P: AsRef<Path>>(path: P, count: usize) -> io::Result<Unlocks> {
+      let mut unlocks = Unlocks::new(count);
+      let file = match File::open(path) {
+         Ok(file) => file,
+         Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(unlocks),
+         Err(err) => return Err(err)
+      };
+      for line in BufReader::new(file).lines() {
+         let line = line?;
+         let parts: Vec<&str> = line.split_whitespace().collect();
+         let (level, record) =
+            match parts.as_slice() {
+               [] => continue,
+               [level, state, best] => {
+                  let level = level.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).ok_or_else(|| invalid_data(&line))?;
+                  let best = best.parse().map_err(|_| invalid_data(&line))?;
+                  match *state {
+                     "open" => (level, CaveRecord { unlocked: true, completed: false, best }),
+                     "done" => (level, CaveRecord { unlocked: true, completed: true, best }),
+                     _ => return Err(invalid_data(&line))
+                  }
+               }
+               _ => return Err(invalid_data(&line))
+            };
+         if level < unlocks.caves.len() {
+            unlocks.caves[level] = record;
+         }
+      }
+      Ok(unlocks)
+   }
+
+   pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+      let temp_path = path.as_ref().with_extension("tmp");
+      {
+         let mut file = File::create(&temp_path)?;
+         for (i, c) in self.caves.iter().enumerate().filter(|&(_, c)| c.unlocked) {
+            writeln!(file, "{} {} {}", i + 1, if c.completed { "done" } else { "open" }, c.best)?;
+         }
+      }
+      fs::rename(&temp_path, path)
+   }
+
+   pub fn is_unlocked(&self, level: usize) -> bool {
+      self.caves.get(level).map_or(false, |c| c.unlocked)
+   }
+
+   pub fn unlock(&mut self, level: usize) {
+      if let Some(c) = self.caves.get_mut(level) {
+         c.unlocked = true;
+      }
+   }
+
+   pub fn complete(&mut self, level: usize, score: Option<i64>, next: &[usize]) {
+      if let (Some(c), Some(score)) = (self.caves.get_mut(level), score) {
+         c.unlocked = true;
+         c.completed = true;
+         c.best = ::std::cmp::max(c.best, score);
+      }
+      for &n in next {
+         self.unlock(n);
+      }
+   }
+}
+
+pub fn password(pack_id: &str, level: usize) -> String {
+   let mut hash: u64 = 0xcbf29ce484222325;
+   for &b in pack_id.as_bytes().iter().chain(format!("#{}", level).as_bytes()) {
+      hash ^= b as u64;
+      hash = hash.wrapping_mul(0x100000001b3);
+   }
+   (0..PASSWORD_LEN)
+      .map(|_| {
+         let letter = PASSWORD_LETTERS[(hash % PASSWORD_LETTERS.len() as u64) as usize];
+         hash /= PASSWORD_LETTERS.len() as u64;
+         letter as char
+      })
+      .collect()
+}
+
+pub fn level_for_password(pack_id: &str, count: usize, code: &str) -> Option<usize> {
+   let code = code.trim().to_uppercase();
+   (0..count).find(|&level| password(pack_id, level) == code)
+}
+
+fn invalid_data(line: &str) -> io::Error {
+   io::Error::new(io::ErrorKind::InvalidData, format!("Cave record not recognised. {}", line))
+}
@@ -1,20 +1,66 @@
 extern crate rand;
 
-use rand::Rng;
+use rand::{Rng, SeedableRng, StdRng};
 use pos::Direction;
 
-pub fn select_random<T>(x: T, y: T) -> T {
-   if rand::thread_rng().gen() { x } else { y }
+#[derive(Clone)]
+pub struct GameRng {
+   seed: usize,
+   draws: u64,
+   rng: StdRng
 }
 
-pub fn select_random_direction() -> Direction {
+impl GameRng {
+   pub fn new(seed: usize) -> GameRng {
+      GameRng { seed, draws: 0, rng: SeedableRng::from_seed(&[seed][..]) }
+   }
+
+   pub fn restore(seed: usize, draws: u64) -> GameRng {
+      let mut rng = GameRng::new(seed);
+      for _ in 0..draws {
+         rng.rng.next_u64();
+      }
+      rng.draws = draws;
+      rng
+   }
+
+   pub fn state(&self) -> (usize, u64) {
+      (self.seed, self.draws)
+   }
+}
+
+impl Rng for GameRng {
+   fn next_u32(&mut self) -> u32 {
+      self.draws += 1;
+      self.rng.next_u32()
+   }
+
+   fn next_u64(&mut self) -> u64 {
+      self.draws += 1;
+      self.rng.next_u64()
+   }
+}
+
+pub fn select_random<T, R: Rng>(rng: &mut R, x: T, y: T) -> T {
+   if rng.gen() { x } else { y }
+}
+
+pub fn select_random_direction<R: Rng>(rng: &mut R) -> Direction {
    let ds = vec![Direction::Up, Direction::Down, Direction::Right, Direction::Left];
-   *rand::thread_rng().choose(&ds).unwrap()
+   *rng.choose(&ds).unwrap()
 }
 
-pub fn calculate_camera_position(map_size: (f64, f64), viewport_size: (f64, f64), player_position: (f64, f64)) -> (f64, f64) {
-   let f = | g, c, p | if g < c { (c - g)/ 2.0 } else { p };
-   let x = f(map_size.0, viewport_size.0, player_position.0);
-   let y = f(map_size.1, viewport_size.1, player_position.1);
-   (x, y)
-}
\ No newline at end of file
+pub fn view_origin(focus: i64, map_len: i64, view_len: i64, wrap: bool) -> i64 {
+   let origin = focus - view_len / 2;
+   if wrap {
+      origin
+   } else if map_len <= view_len {
+      -(view_len - map_len) / 2
+   } else if origin < 0 {
+      0
+   } else if origin + view_len > map_len {
+      map_len - view_len
+   } else {
+      origin
+   }
+}